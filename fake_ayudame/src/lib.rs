@@ -0,0 +1,104 @@
+//! A fake libayudame for end-to-end tests of the wrapper.
+//!
+//! It exports the same `ayu_event_*` symbols as the real library and appends every call,
+//! formatted like `Display for Event`, to the file named by `FAKE_AYUDAME_LOG`.
+//! If the variable is not set, calls are written to stderr instead.
+
+use std::{
+    env,
+    ffi::CStr,
+    fs::OpenOptions,
+    io::Write,
+    os::raw::c_char,
+};
+
+use ayudame_wrapper::Event;
+
+pub const LOG_ENV: &str = "FAKE_AYUDAME_LOG";
+
+fn log(event: Event) {
+    match env::var_os(LOG_ENV) {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(&path);
+            match file {
+                // a single write per call, so lines of calls from several threads do not interleave
+                Ok(mut file) => { let _ = file.write_all(format!("{}\n", event).as_bytes()); },
+                Err(e) => eprintln!("fake ayudame: unable to open {}: {}", path.to_string_lossy(), e),
+            }
+        },
+        None => eprintln!("{}", event),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_preinit(rt: u64) {
+    log(Event::PreInit { rt })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_init(n_threads: u64) {
+    log(Event::Init { n_threads })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_addtask(task_id: u64, func_id: u64, priority: u64, scope_id: u64) {
+    log(Event::AddTask { task_id, func_id, priority, scope_id })
+}
+
+/// # Safety
+///
+/// `name` must be null or point to a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn ayu_event_registerfunction(func_id: u64, name: *mut c_char) {
+    let name = if name.is_null() {
+        Default::default()
+    } else {
+        CStr::from_ptr(name).to_owned()
+    };
+    log(Event::RegisterFunction { func_id, name })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_adddependency(to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64) {
+    log(Event::AddDependency { to_id, from_id, memaddr, orig_memaddr })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_addtasktoqueue(task_id: u64, thread_id: u64) {
+    log(Event::AddTaskToQueue { task_id, thread_id })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_preruntask(task_id: u64, thread_id: u64) {
+    log(Event::PreRunTask { task_id, thread_id })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_runtask(task_id: u64) {
+    log(Event::RunTask { task_id })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_postruntask(task_id: u64) {
+    log(Event::PostRunTask { task_id })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_removetask(task_id: u64) {
+    log(Event::RemoveTask { task_id })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_barrier() {
+    log(Event::Barrier)
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_waiton(task_id: u64) {
+    log(Event::WaitOn { task_id })
+}
+
+#[no_mangle]
+pub extern "C" fn ayu_event_finish() {
+    log(Event::Finish)
+}
@@ -0,0 +1,402 @@
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+
+use crate::{generator::EliminationOrder, Durations, Execution, Generator, InputTypes, RemovalPolicy, SchedulingPolicy, MAX_THREADS};
+
+/// Syntax of all commands, shown to the user
+pub const COMMAND_SYNTAX: &str =
+"Commands:
+    preinit [<runtime id>]
+    init [<thread count>]
+    task [id=<id>] [crit] [thread=<id>] [label=<function id>]
+    function [<name>]
+    dep <parent id> -> <child id>
+    undep <parent id> -> <child id>
+    queue <task id>
+    prerun <task id>
+    run <task id>
+    postrun <task id>
+    remove <task id> [refuse|cascade|detach]
+    barrier
+    waiton <task id>
+    waiton children <task id>
+    finish
+    print
+    export <path>
+    generate layered|random|bounded [tasks=<n>] [density=<p>] [labels=<n>] [seed=<n>]
+        with [layers=<n>] for layered and [max-in=<n>] [max-out=<n>] for bounded graphs
+    generate cholesky|lu|qr [tiles=<n>]
+    generate forkjoin|pipeline|wavefront|stencil|mapreduce [width=<n>] [depth=<n>] [iterations=<n>]
+    import [order=natural|mindegree] <path to .mtx file>
+    schedule [fifo|lifo|critical|affinity|steal]
+    simulate [policy=<policy>] [duration=<ticks>] [<task id>=<ticks> ...] [<label>=<ticks> ...]
+    execute [policy=<policy>] [tick=<ms>] [spin] [duration=<ticks>] [<task id>=<ticks> ...] [<label>=<ticks> ...]
+    quit
+Empty lines and lines starting with # are ignored.
+When typed interactively, commands given without their arguments ask for them.";
+
+/// A single command, corresponding to one event (or printing the state, generating or simulating a graph or ending the session)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    PreInit { runtime_id: Option<u64> },
+    Init { n_threads: Option<u64> },
+    AddTask { task_id: Option<u64>, is_critical: bool, thread_id: u64, function_id: Option<u64> },
+    RegisterFunction { name: String },
+    AddDependency { parent_id: u64, child_id: u64 },
+    /// Only changes the state, there is no event for removing dependencies
+    RemoveDependency { parent_id: u64, child_id: u64 },
+    AddTaskToQueue { task_id: u64 },
+    PreRunTask { task_id: u64 },
+    RunTask { task_id: u64 },
+    PostRunTask { task_id: u64 },
+    RemoveTask { task_id: u64, policy: Option<RemovalPolicy> },
+    Barrier,
+    WaitOn { target: WaitTarget },
+    Finish,
+    PrintState,
+    /// Writes the task graph to a file in the DOT format
+    Export { path: PathBuf },
+    /// Creates a whole task graph at once
+    Generate { generator: Generator },
+    /// Creates the task graph of a sparse Cholesky factorization of a matrix read from a MatrixMarket file
+    Import { path: PathBuf, order: EliminationOrder },
+    /// Queues and pre-runs all ready tasks, on the threads the scheduling policy chooses
+    Schedule { policy: Option<SchedulingPolicy> },
+    /// Runs all unfinished tasks in virtual time, sending their lifecycle events
+    Simulate { durations: Durations, policy: Option<SchedulingPolicy> },
+    /// Runs all unfinished tasks on one worker thread per ayudame thread, which send their lifecycle events
+    Execute { execution: Execution, policy: Option<SchedulingPolicy> },
+    /// Ends the session
+    Quit,
+}
+
+/// The tasks a `waiton` is sent for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitTarget {
+    Task(u64),
+    /// All tasks depending on the given task
+    ChildrenOf(u64),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    InvalidArgument(String),
+    UnexpectedArgument(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnknownCommand(cmd) => write!(f, "Unknown command: {}", cmd),
+            ParseError::MissingArgument(arg) => write!(f, "Missing argument: {}", arg),
+            ParseError::InvalidArgument(arg) => write!(f, "Invalid argument: {}", arg),
+            ParseError::UnexpectedArgument(arg) => write!(f, "Unexpected argument: {}", arg),
+        }
+    }
+}
+
+impl std::error::Error for ParseError { }
+
+impl Command {
+    /// The task whose lifecycle the command advances, if any
+    pub fn task_id(&self) -> Option<u64> {
+        match *self {
+            Command::AddTaskToQueue { task_id }
+            | Command::PreRunTask { task_id }
+            | Command::RunTask { task_id }
+            | Command::PostRunTask { task_id }
+            | Command::RemoveTask { task_id, .. } => Some(task_id),
+            _ => None,
+        }
+    }
+
+    /// Parses a line of a script.
+    /// Returns `Ok(None)` for empty lines and comments.
+    pub fn parse_line(line: &str) -> Result<Option<Self>, ParseError> {
+        match line.trim() {
+            "" => Ok(None),
+            l if l.starts_with('#') => Ok(None),
+            l => l.parse().map(Some),
+        }
+    }
+}
+
+/// Returns the type of event whose arguments still have to be asked for,
+/// if `line` consists only of the keyword of a command that takes arguments.
+pub fn missing_arguments(line: &str) -> Option<InputTypes> {
+    match line.trim() {
+        "task" => Some(InputTypes::AddTask),
+        "function" => Some(InputTypes::RegisterFunction),
+        "dep" => Some(InputTypes::AddDependency),
+        "queue" => Some(InputTypes::AddTaskToQueue),
+        "prerun" => Some(InputTypes::PreRunTask),
+        "run" => Some(InputTypes::RunTask),
+        "postrun" => Some(InputTypes::PostRunTask),
+        "remove" => Some(InputTypes::RemoveTask),
+        "waiton" => Some(InputTypes::WaitOn),
+        _ => None,
+    }
+}
+
+impl FromStr for Command {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (keyword, rest) = s.split_once(char::is_whitespace).unwrap_or((s, ""));
+        let mut args = Arguments::new(rest);
+
+        let command = match keyword {
+            "preinit" => Command::PreInit { runtime_id: args.next().map(parse_id).transpose()? },
+            "init" => Command::Init { n_threads: args.next().map(parse_count).transpose()? },
+            "task" => {
+                let (mut task_id, mut is_critical, mut thread_id, mut function_id) = (None, false, 0, None);
+                for arg in args.by_ref() {
+                    match arg.split_once('=') {
+                        None if arg == "crit" => is_critical = true,
+                        Some(("id", id)) => task_id = Some(parse_id(id)?),
+                        Some(("thread", id)) => thread_id = parse_id(id)?,
+                        Some(("label", id)) => function_id = Some(parse_id(id)?),
+                        _ => return Err(ParseError::UnexpectedArgument(arg.to_string())),
+                    }
+                }
+                Command::AddTask { task_id, is_critical, thread_id, function_id }
+            },
+            // the name may contain whitespace, so take the whole rest of the line
+            "function" => return Ok(Command::RegisterFunction { name: rest.trim().to_string() }),
+            "dep" | "undep" => {
+                let parent_id = args.id("parent id")?;
+                if args.peek() == Some("->") {
+                    args.next();
+                }
+                let child_id = args.id("child id")?;
+                match keyword {
+                    "dep" => Command::AddDependency { parent_id, child_id },
+                    _ => Command::RemoveDependency { parent_id, child_id },
+                }
+            },
+            "queue" => Command::AddTaskToQueue { task_id: args.id("task id")? },
+            "prerun" => Command::PreRunTask { task_id: args.id("task id")? },
+            "run" => Command::RunTask { task_id: args.id("task id")? },
+            "postrun" => Command::PostRunTask { task_id: args.id("task id")? },
+            "remove" => Command::RemoveTask { task_id: args.id("task id")?, policy: args.next().map(str::parse).transpose()? },
+            "barrier" => Command::Barrier,
+            "waiton" => match args.peek() {
+                Some("children") => {
+                    args.next();
+                    Command::WaitOn { target: WaitTarget::ChildrenOf(args.id("task id")?) }
+                },
+                _ => Command::WaitOn { target: WaitTarget::Task(args.id("task id")?) },
+            },
+            "finish" => Command::Finish,
+            "print" => Command::PrintState,
+            "generate" => return Ok(Command::Generate { generator: rest.parse()? }),
+            "schedule" => Command::Schedule { policy: args.next().map(str::parse).transpose()? },
+            "simulate" | "execute" => {
+                let (policy, rest): (Vec<_>, Vec<_>) = rest.split_whitespace().partition(|word| word.starts_with("policy="));
+                let policy = policy.last().and_then(|word| word.strip_prefix("policy=")).map(str::parse).transpose()?;
+                return Ok(match keyword {
+                    "simulate" => Command::Simulate { durations: rest.join(" ").parse()?, policy },
+                    _ => Command::Execute { execution: rest.join(" ").parse()?, policy },
+                });
+            },
+            "quit" => Command::Quit,
+            // like names, paths may contain whitespace
+            "export" => return match rest.trim() {
+                "" => Err(ParseError::MissingArgument("path")),
+                path => Ok(Command::Export { path: path.into() }),
+            },
+            "import" => {
+                let rest = rest.trim();
+                let (order, path) = match rest.strip_prefix("order=") {
+                    Some(order) => {
+                        let (order, path) = order.split_once(char::is_whitespace).unwrap_or((order, ""));
+                        (order.parse()?, path.trim())
+                    },
+                    None => (EliminationOrder::default(), rest),
+                };
+                return match path {
+                    "" => Err(ParseError::MissingArgument("path")),
+                    path => Ok(Command::Import { path: path.into(), order }),
+                };
+            },
+            _ => return Err(ParseError::UnknownCommand(keyword.to_string())),
+        };
+
+        match args.next() {
+            Some(arg) => Err(ParseError::UnexpectedArgument(arg.to_string())),
+            None => Ok(command),
+        }
+    }
+}
+
+fn parse_id(s: &str) -> Result<u64, ParseError> {
+    s.parse().map_err(|_| ParseError::InvalidArgument(s.to_string()))
+}
+
+/// Parses a number of threads, from 1 to `MAX_THREADS`
+fn parse_count(s: &str) -> Result<u64, ParseError> {
+    match s.parse() {
+        Ok(n) if (1..=MAX_THREADS).contains(&n) => Ok(n),
+        _ => Err(ParseError::InvalidArgument(s.to_string())),
+    }
+}
+
+/// Whitespace separated arguments, where `->` is always a separate argument
+struct Arguments<'a> {
+    args: std::iter::Peekable<std::vec::IntoIter<&'a str>>,
+}
+
+impl<'a> Arguments<'a> {
+    fn new(s: &'a str) -> Self {
+        let mut args = Vec::new();
+        for word in s.split_whitespace() {
+            let mut parts = word.split("->").peekable();
+            while let Some(part) = parts.next() {
+                if !part.is_empty() {
+                    args.push(part);
+                }
+                if parts.peek().is_some() {
+                    args.push("->");
+                }
+            }
+        }
+
+        Self { args: args.into_iter().peekable() }
+    }
+
+    fn peek(&mut self) -> Option<&'a str> {
+        self.args.peek().copied()
+    }
+
+    fn id(&mut self, name: &'static str) -> Result<u64, ParseError> {
+        parse_id(self.args.next().ok_or(ParseError::MissingArgument(name))?)
+    }
+}
+
+impl<'a> Iterator for Arguments<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.args.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{generator::EliminationOrder, InputTypes, RemovalPolicy, SchedulingPolicy};
+
+    use super::{missing_arguments, Command, ParseError, WaitTarget};
+
+    #[test]
+    fn parse_simple_commands() {
+        assert_eq!("preinit".parse(), Ok(Command::PreInit { runtime_id: None }));
+        assert_eq!("preinit 3".parse(), Ok(Command::PreInit { runtime_id: Some(3) }));
+        assert_eq!("init 64".parse(), Ok(Command::Init { n_threads: Some(64) }));
+        assert_eq!("init 0".parse::<Command>(), Err(ParseError::InvalidArgument("0".to_string())));
+        assert_eq!("init 4096".parse(), Ok(Command::Init { n_threads: Some(4096) }));
+        assert_eq!("init 4097".parse::<Command>(), Err(ParseError::InvalidArgument("4097".to_string())));
+        assert_eq!("  barrier ".parse(), Ok(Command::Barrier));
+        assert_eq!("run 4".parse(), Ok(Command::RunTask { task_id: 4 }));
+        assert_eq!("remove 4".parse(), Ok(Command::RemoveTask { task_id: 4, policy: None }));
+        assert_eq!("remove 4 cascade".parse(), Ok(Command::RemoveTask { task_id: 4, policy: Some(RemovalPolicy::Cascade) }));
+    }
+
+    #[test]
+    fn parse_task() {
+        assert_eq!("task".parse(), Ok(Command::AddTask { task_id: None, is_critical: false, thread_id: 0, function_id: None }));
+        assert_eq!("task crit thread=1 label=3".parse(), Ok(Command::AddTask { task_id: None, is_critical: true, thread_id: 1, function_id: Some(3) }));
+        assert_eq!("task id=7".parse(), Ok(Command::AddTask { task_id: Some(7), is_critical: false, thread_id: 0, function_id: None }));
+        assert_eq!("task thread=x".parse::<Command>(), Err(ParseError::InvalidArgument("x".to_string())));
+        assert_eq!("task urgent".parse::<Command>(), Err(ParseError::UnexpectedArgument("urgent".to_string())));
+    }
+
+    #[test]
+    fn parse_function() {
+        assert_eq!("function my kernel".parse(), Ok(Command::RegisterFunction { name: "my kernel".to_string() }));
+        assert_eq!("function".parse(), Ok(Command::RegisterFunction { name: String::new() }));
+    }
+
+    #[test]
+    fn parse_dependency() {
+        let dep = Ok(Command::AddDependency { parent_id: 4, child_id: 7 });
+        assert_eq!("dep 4 -> 7".parse(), dep);
+        assert_eq!("dep 4->7".parse(), dep);
+        assert_eq!("dep 4 7".parse(), dep);
+        assert_eq!("dep 4 ->".parse::<Command>(), Err(ParseError::MissingArgument("child id")));
+        assert_eq!("undep 4 -> 7".parse(), Ok(Command::RemoveDependency { parent_id: 4, child_id: 7 }));
+    }
+
+    #[test]
+    fn parse_export() {
+        assert_eq!("export my graph.dot".parse(), Ok(Command::Export { path: "my graph.dot".into() }));
+        assert_eq!("export".parse::<Command>(), Err(ParseError::MissingArgument("path")));
+    }
+
+    #[test]
+    fn parse_import() {
+        assert_eq!("import bcsstk01.mtx".parse(), Ok(Command::Import { path: "bcsstk01.mtx".into(), order: EliminationOrder::Natural }));
+        assert_eq!(
+            "import order=mindegree my matrix.mtx".parse(),
+            Ok(Command::Import { path: "my matrix.mtx".into(), order: EliminationOrder::MinimumDegree }),
+        );
+        assert_eq!("import order=mindegree".parse::<Command>(), Err(ParseError::MissingArgument("path")));
+        assert_eq!("import order=metis a.mtx".parse::<Command>(), Err(ParseError::InvalidArgument("metis".to_string())));
+    }
+
+    #[test]
+    fn parse_wait_on() {
+        assert_eq!("waiton 3".parse(), Ok(Command::WaitOn { target: WaitTarget::Task(3) }));
+        assert_eq!("waiton children 3".parse(), Ok(Command::WaitOn { target: WaitTarget::ChildrenOf(3) }));
+        assert_eq!("waiton children".parse::<Command>(), Err(ParseError::MissingArgument("task id")));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("jump".parse::<Command>(), Err(ParseError::UnknownCommand("jump".to_string())));
+        assert_eq!("run".parse::<Command>(), Err(ParseError::MissingArgument("task id")));
+        assert_eq!("run 1 2".parse::<Command>(), Err(ParseError::UnexpectedArgument("2".to_string())));
+        assert_eq!("run -1".parse::<Command>(), Err(ParseError::InvalidArgument("-1".to_string())));
+    }
+
+    #[test]
+    fn missing_arguments_only_for_bare_keywords() {
+        assert!(matches!(missing_arguments(" task "), Some(InputTypes::AddTask)));
+        assert!(matches!(missing_arguments("dep"), Some(InputTypes::AddDependency)));
+        assert!(missing_arguments("task crit").is_none());
+        assert!(missing_arguments("run 4").is_none());
+        assert!(missing_arguments("barrier").is_none());
+    }
+
+    #[test]
+    fn parse_line_skips_comments() {
+        assert_eq!(Command::parse_line(""), Ok(None));
+        assert_eq!(Command::parse_line("   # a comment"), Ok(None));
+        assert_eq!(Command::parse_line("finish"), Ok(Some(Command::Finish)));
+        assert_eq!(Command::parse_line(" quit "), Ok(Some(Command::Quit)));
+        assert!(matches!(Command::parse_line("generate bounded max-in=3"), Ok(Some(Command::Generate { .. }))));
+        assert!(matches!(Command::parse_line("simulate duration=2 GEMM=5"), Ok(Some(Command::Simulate { policy: None, .. }))));
+    }
+
+    #[test]
+    fn parse_scheduling() {
+        assert_eq!("schedule".parse(), Ok(Command::Schedule { policy: None }));
+        assert_eq!("schedule steal".parse(), Ok(Command::Schedule { policy: Some(SchedulingPolicy::WorkStealing) }));
+        assert_eq!("schedule steal 2".parse::<Command>(), Err(ParseError::UnexpectedArgument("2".to_string())));
+
+        let Ok(Command::Simulate { durations, policy }) = "simulate GEMM=3 policy=lifo".parse() else {
+            panic!("simulate with a policy is a valid command");
+        };
+        assert_eq!(policy, Some(SchedulingPolicy::Lifo));
+        assert_eq!(durations.labels.get("GEMM"), Some(&3));
+        assert_eq!("simulate policy=random".parse::<Command>(), Err(ParseError::InvalidArgument("random".to_string())));
+
+        let Ok(Command::Execute { execution, policy }) = "execute policy=steal tick=2 GEMM=3".parse() else {
+            panic!("execute with a policy is a valid command");
+        };
+        assert_eq!(policy, Some(SchedulingPolicy::WorkStealing));
+        assert_eq!(execution.tick, std::time::Duration::from_millis(2));
+        assert_eq!(execution.durations.labels.get("GEMM"), Some(&3));
+    }
+}
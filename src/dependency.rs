@@ -0,0 +1,132 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{command::ParseError, TransitionError};
+
+/// The memory addresses sent with a dependency, made up from the ids of the tasks
+pub fn memory_addresses(parent_id: u64, child_id: u64) -> (u64, u64) {
+    (0xffffeeee | parent_id, 0xffffeee | child_id)
+}
+
+/// What happens to the dependents of a task when it is removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RemovalPolicy {
+    /// Refuse to remove tasks that still have dependents
+    Refuse,
+    /// Remove all descendants of the task as well
+    Cascade,
+    /// Remove only the task and its dependencies, keeping its dependents
+    #[default]
+    Detach,
+}
+
+/// What happens when a dependency is added that already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Refuse the dependency
+    #[default]
+    Error,
+    /// Do nothing, without reporting an error
+    Ignore,
+    /// Send the dependency again
+    Resend,
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(DuplicatePolicy::Error),
+            "ignore" => Ok(DuplicatePolicy::Ignore),
+            "resend" => Ok(DuplicatePolicy::Resend),
+            _ => Err(ParseError::InvalidArgument(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for RemovalPolicy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "refuse" => Ok(RemovalPolicy::Refuse),
+            "cascade" => Ok(RemovalPolicy::Cascade),
+            "detach" => Ok(RemovalPolicy::Detach),
+            _ => Err(ParseError::InvalidArgument(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DependencyError {
+    TaskNotFound(u64),
+    /// The dependency would close a cycle, given as the ids along it, starting and ending with the parent
+    Cycle(Vec<u64>),
+    Duplicate { parent_id: u64, child_id: u64 },
+    NotFound { parent_id: u64, child_id: u64 },
+}
+
+impl Display for DependencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyError::TaskNotFound(id) => write!(f, "Task with id: {} not found.", id),
+            DependencyError::Cycle(path) => {
+                let path: Vec<_> = path.iter().map(|id| id.to_string()).collect();
+                write!(f, "Dependency {} -> {} would create the cycle {}.", path[0], path[1], path.join(" -> "))
+            },
+            DependencyError::Duplicate { parent_id, child_id } => write!(f, "Dependency {} -> {} already exists.", parent_id, child_id),
+            DependencyError::NotFound { parent_id, child_id } => write!(f, "Dependency {} -> {} not found.", parent_id, child_id),
+        }
+    }
+}
+
+impl std::error::Error for DependencyError { }
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RemovalError {
+    TaskNotFound(u64),
+    HasDependents { task_id: u64, dependents: Vec<u64> },
+    /// The task, or one of the descendants removed with it, cannot be removed in its current state
+    IllegalTransition(TransitionError),
+}
+
+impl Display for RemovalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RemovalError::TaskNotFound(id) => write!(f, "Task with id: {} not found.", id),
+            RemovalError::HasDependents { task_id, dependents } => {
+                let dependents: Vec<_> = dependents.iter().map(|id| id.to_string()).collect();
+                write!(f, "Task {} still has the dependents {}, remove them first or use the cascade or detach policy.", task_id, dependents.join(", "))
+            },
+            RemovalError::IllegalTransition(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RemovalError { }
+
+#[cfg(test)]
+mod tests {
+    use crate::command::ParseError;
+
+    use super::{DependencyError, DuplicatePolicy, RemovalError, RemovalPolicy};
+
+    #[test]
+    fn cycle_display() {
+        let e = DependencyError::Cycle(vec![3, 1, 2, 3]);
+        assert_eq!(e.to_string(), "Dependency 3 -> 1 would create the cycle 3 -> 1 -> 2 -> 3.");
+    }
+
+    #[test]
+    fn policy_parse() {
+        assert_eq!("cascade".parse(), Ok(RemovalPolicy::Cascade));
+        assert_eq!("keep".parse::<RemovalPolicy>(), Err(ParseError::InvalidArgument("keep".to_string())));
+        assert_eq!("resend".parse(), Ok(DuplicatePolicy::Resend));
+    }
+
+    #[test]
+    fn has_dependents_display() {
+        let e = RemovalError::HasDependents { task_id: 1, dependents: vec![2, 3] };
+        assert_eq!(e.to_string(), "Task 1 still has the dependents 2, 3, remove them first or use the cascade or detach policy.");
+    }
+}
@@ -0,0 +1,254 @@
+use std::{ffi::CString, fmt::Display, str::FromStr};
+
+use crate::{command::ParseError, EventSink};
+
+/// A single Ayudame event together with the raw arguments passed over FFI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    PreInit { rt: u64 },
+    Init { n_threads: u64 },
+    AddTask { task_id: u64, func_id: u64, priority: u64, scope_id: u64 },
+    RegisterFunction { func_id: u64, name: CString },
+    AddDependency { to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64 },
+    AddTaskToQueue { task_id: u64, thread_id: u64 },
+    PreRunTask { task_id: u64, thread_id: u64 },
+    RunTask { task_id: u64 },
+    PostRunTask { task_id: u64 },
+    RemoveTask { task_id: u64 },
+    Barrier,
+    WaitOn { task_id: u64 },
+    Finish,
+}
+
+impl Event {
+    /// The name of the ayudame function this event is sent with
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::PreInit { .. } => "ayu_event_preinit",
+            Event::Init { .. } => "ayu_event_init",
+            Event::AddTask { .. } => "ayu_event_addtask",
+            Event::RegisterFunction { .. } => "ayu_event_registerfunction",
+            Event::AddDependency { .. } => "ayu_event_adddependency",
+            Event::AddTaskToQueue { .. } => "ayu_event_addtasktoqueue",
+            Event::PreRunTask { .. } => "ayu_event_preruntask",
+            Event::RunTask { .. } => "ayu_event_runtask",
+            Event::PostRunTask { .. } => "ayu_event_postruntask",
+            Event::RemoveTask { .. } => "ayu_event_removetask",
+            Event::Barrier => "ayu_event_barrier",
+            Event::WaitOn { .. } => "ayu_event_waiton",
+            Event::Finish => "ayu_event_finish",
+        }
+    }
+
+    /// Calls the corresponding method of `sink`
+    pub fn send_to(&self, sink: &mut dyn EventSink) {
+        match *self {
+            Event::PreInit { rt } => sink.pre_init(rt),
+            Event::Init { n_threads } => sink.init(n_threads),
+            Event::AddTask { task_id, func_id, priority, scope_id } => sink.add_task(task_id, func_id, priority, scope_id),
+            Event::RegisterFunction { func_id, ref name } => sink.register_function(func_id, name),
+            Event::AddDependency { to_id, from_id, memaddr, orig_memaddr } => sink.add_dependency(to_id, from_id, memaddr, orig_memaddr),
+            Event::AddTaskToQueue { task_id, thread_id } => sink.add_task_to_queue(task_id, thread_id),
+            Event::PreRunTask { task_id, thread_id } => sink.pre_run_task(task_id, thread_id),
+            Event::RunTask { task_id } => sink.run_task(task_id),
+            Event::PostRunTask { task_id } => sink.post_run_task(task_id),
+            Event::RemoveTask { task_id } => sink.remove_task(task_id),
+            Event::Barrier => sink.barrier(),
+            Event::WaitOn { task_id } => sink.wait_on(task_id),
+            Event::Finish => sink.finish(),
+        }
+    }
+}
+
+/// Formats the event as `<name> <arg>=<value> ...`, e.g.
+/// `ayu_event_addtask task_id=1 func_id=0 priority=0 scope_id=0`.
+/// Names of functions are quoted, with `"` and `\` escaped by a backslash.
+impl Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())?;
+        match self {
+            Event::PreInit { rt } => write!(f, " rt={}", rt),
+            Event::Init { n_threads } => write!(f, " n_threads={}", n_threads),
+            Event::AddTask { task_id, func_id, priority, scope_id } =>
+                write!(f, " task_id={} func_id={} priority={} scope_id={}", task_id, func_id, priority, scope_id),
+            Event::RegisterFunction { func_id, name } => {
+                write!(f, " func_id={} name=\"", func_id)?;
+                for c in name.to_string_lossy().chars() {
+                    match c {
+                        '"' | '\\' => write!(f, "\\{}", c)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            },
+            Event::AddDependency { to_id, from_id, memaddr, orig_memaddr } =>
+                write!(f, " to_id={} from_id={} memaddr={:#x} orig_memaddr={:#x}", to_id, from_id, memaddr, orig_memaddr),
+            Event::AddTaskToQueue { task_id, thread_id } | Event::PreRunTask { task_id, thread_id } =>
+                write!(f, " task_id={} thread_id={}", task_id, thread_id),
+            Event::RunTask { task_id } | Event::PostRunTask { task_id } | Event::RemoveTask { task_id } | Event::WaitOn { task_id } =>
+                write!(f, " task_id={}", task_id),
+            Event::Barrier | Event::Finish => Ok(()),
+        }
+    }
+}
+
+/// Parses the format written by `Display for Event`
+impl FromStr for Event {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, rest) = s.split_once(' ').unwrap_or((s, ""));
+        let mut args = EventArguments::new(rest)?;
+
+        let event = match name {
+            "ayu_event_preinit" => Event::PreInit { rt: args.u64("rt")? },
+            "ayu_event_init" => Event::Init { n_threads: args.u64("n_threads")? },
+            "ayu_event_addtask" => Event::AddTask {
+                task_id: args.u64("task_id")?,
+                func_id: args.u64("func_id")?,
+                priority: args.u64("priority")?,
+                scope_id: args.u64("scope_id")?,
+            },
+            "ayu_event_registerfunction" => Event::RegisterFunction {
+                func_id: args.u64("func_id")?,
+                name: {
+                    let name = args.value("name")?;
+                    CString::new(name.clone()).map_err(|_| ParseError::InvalidArgument(name))?
+                },
+            },
+            "ayu_event_adddependency" => Event::AddDependency {
+                to_id: args.u64("to_id")?,
+                from_id: args.u64("from_id")?,
+                memaddr: args.u64("memaddr")?,
+                orig_memaddr: args.u64("orig_memaddr")?,
+            },
+            "ayu_event_addtasktoqueue" => Event::AddTaskToQueue { task_id: args.u64("task_id")?, thread_id: args.u64("thread_id")? },
+            "ayu_event_preruntask" => Event::PreRunTask { task_id: args.u64("task_id")?, thread_id: args.u64("thread_id")? },
+            "ayu_event_runtask" => Event::RunTask { task_id: args.u64("task_id")? },
+            "ayu_event_postruntask" => Event::PostRunTask { task_id: args.u64("task_id")? },
+            "ayu_event_removetask" => Event::RemoveTask { task_id: args.u64("task_id")? },
+            "ayu_event_barrier" => Event::Barrier,
+            "ayu_event_waiton" => Event::WaitOn { task_id: args.u64("task_id")? },
+            "ayu_event_finish" => Event::Finish,
+            _ => return Err(ParseError::UnknownCommand(name.to_string())),
+        };
+
+        match args.args.next() {
+            Some((key, _)) => Err(ParseError::UnexpectedArgument(key)),
+            None => Ok(event),
+        }
+    }
+}
+
+/// The `key=value` pairs following the name of an event
+struct EventArguments {
+    args: std::vec::IntoIter<(String, String)>,
+}
+
+impl EventArguments {
+    fn new(s: &str) -> Result<Self, ParseError> {
+        let mut args = Vec::new();
+        let mut chars = s.trim().chars().peekable();
+
+        while chars.peek().is_some() {
+            let key: String = chars.by_ref().take_while(|c| *c != '=').collect();
+            let mut value = String::new();
+
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => value.extend(chars.next()),
+                        Some(c) => value.push(c),
+                        None => return Err(ParseError::InvalidArgument(format!("{}=\"{}", key, value))),
+                    }
+                }
+            } else {
+                value.extend(chars.by_ref().take_while(|c| *c != ' '));
+            }
+
+            while chars.next_if_eq(&' ').is_some() { }
+            args.push((key.trim().to_string(), value));
+        }
+
+        Ok(Self { args: args.into_iter() })
+    }
+
+    fn value(&mut self, key: &'static str) -> Result<String, ParseError> {
+        match self.args.next() {
+            Some((k, value)) if k == key => Ok(value),
+            Some((k, _)) => Err(ParseError::UnexpectedArgument(k)),
+            None => Err(ParseError::MissingArgument(key)),
+        }
+    }
+
+    fn u64(&mut self, key: &'static str) -> Result<u64, ParseError> {
+        let value = self.value(key)?;
+        let parsed = match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => value.parse(),
+        };
+        parsed.map_err(|_| ParseError::InvalidArgument(format!("{}={}", key, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use crate::command::ParseError;
+
+    use super::Event;
+
+    #[test]
+    fn event_display() {
+        let event = Event::AddTask { task_id: 1, func_id: 2, priority: 1, scope_id: 0 };
+        assert_eq!(event.to_string(), "ayu_event_addtask task_id=1 func_id=2 priority=1 scope_id=0");
+
+        let event = Event::AddDependency { to_id: 0, from_id: 1, memaddr: 0xffffeeee, orig_memaddr: 0xffffeef };
+        assert_eq!(event.to_string(), "ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef");
+
+        assert_eq!(Event::Finish.to_string(), "ayu_event_finish");
+    }
+
+    #[test]
+    fn event_display_escapes_name() {
+        let event = Event::RegisterFunction { func_id: 0, name: CString::new("a \"b\" \\c").unwrap() };
+        assert_eq!(event.to_string(), "ayu_event_registerfunction func_id=0 name=\"a \\\"b\\\" \\\\c\"");
+    }
+
+    #[test]
+    fn event_parse_roundtrip() {
+        let events = [
+            Event::PreInit { rt: 0 },
+            Event::Init { n_threads: 64 },
+            Event::AddTask { task_id: 1, func_id: 2, priority: 1, scope_id: 3 },
+            Event::RegisterFunction { func_id: 0, name: CString::new("a \"b\" \\c = d").unwrap() },
+            Event::RegisterFunction { func_id: 1, name: CString::default() },
+            Event::AddDependency { to_id: 0, from_id: 1, memaddr: 0xffffeeee, orig_memaddr: 0xffffeef },
+            Event::AddTaskToQueue { task_id: 1, thread_id: 7 },
+            Event::PreRunTask { task_id: 1, thread_id: 7 },
+            Event::RunTask { task_id: 1 },
+            Event::PostRunTask { task_id: 1 },
+            Event::RemoveTask { task_id: 1 },
+            Event::Barrier,
+            Event::WaitOn { task_id: 1 },
+            Event::Finish,
+        ];
+
+        for event in events {
+            assert_eq!(event.to_string().parse(), Ok(event));
+        }
+    }
+
+    #[test]
+    fn event_parse_errors() {
+        assert_eq!("ayu_event_jump".parse::<Event>(), Err(ParseError::UnknownCommand("ayu_event_jump".to_string())));
+        assert_eq!("ayu_event_runtask".parse::<Event>(), Err(ParseError::MissingArgument("task_id")));
+        assert_eq!("ayu_event_runtask id=1".parse::<Event>(), Err(ParseError::UnexpectedArgument("id".to_string())));
+        assert_eq!("ayu_event_runtask task_id=x".parse::<Event>(), Err(ParseError::InvalidArgument("task_id=x".to_string())));
+        assert_eq!("ayu_event_finish task_id=1".parse::<Event>(), Err(ParseError::UnexpectedArgument("task_id".to_string())));
+    }
+}
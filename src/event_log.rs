@@ -0,0 +1,290 @@
+use std::{
+    fmt::Display,
+    io::{BufRead, Write},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{command::ParseError, Event, EventSink};
+
+/// First line written to every event log
+pub const LOG_HEADER: &str = "# ayudame_wrapper event log: <sequence number> <unix time> <event> <arguments>";
+
+/// Writes every event to a log before passing it on to another sink.
+///
+/// Each event is written on its own line, as its sequence number, the wall-clock time
+/// in seconds since the unix epoch and the event in the format of `Display for Event`:
+/// `3 1697040000.123456 ayu_event_addtask task_id=0 func_id=0 priority=1 scope_id=0`
+pub struct RecordingSink<S: EventSink, W: Write> {
+    inner: S,
+    writer: W,
+    seq: u64,
+}
+
+impl<S: EventSink, W: Write> RecordingSink<S, W> {
+    pub fn new(inner: S, mut writer: W) -> Self {
+        let _ = writeln!(writer, "{}", LOG_HEADER);
+        Self { inner, writer, seq: 0 }
+    }
+
+    pub fn into_inner(self) -> (S, W) {
+        (self.inner, self.writer)
+    }
+
+    fn record(&mut self, event: Event) {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+        let _ = writeln!(self.writer, "{} {}.{:06} {}", self.seq, time.as_secs(), time.subsec_micros(), event);
+        let _ = self.writer.flush();
+        self.seq += 1;
+
+        event.send_to(&mut self.inner);
+    }
+}
+
+impl<S: EventSink, W: Write> EventSink for RecordingSink<S, W> {
+    impl_event_sink_via!(record);
+}
+
+/// An event read from an event log
+#[derive(Debug, PartialEq, Eq)]
+pub struct LoggedEvent {
+    pub seq: u64,
+    /// Time since the unix epoch at which the event was sent
+    pub time: Duration,
+    pub event: Event,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LogError {
+    Read(String),
+    Parse { line: usize, error: ParseError },
+}
+
+impl Display for LogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogError::Read(e) => write!(f, "Unable to read event log: {}", e),
+            LogError::Parse { line, error } => write!(f, "Invalid event in line {}: {}", line, error),
+        }
+    }
+}
+
+impl std::error::Error for LogError { }
+
+impl std::str::FromStr for LoggedEvent {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, ' ');
+
+        let seq = parts.next().filter(|s| !s.is_empty()).ok_or(ParseError::MissingArgument("sequence number"))?;
+        let seq = seq.parse().map_err(|_| ParseError::InvalidArgument(seq.to_string()))?;
+
+        let time = parts.next().ok_or(ParseError::MissingArgument("time"))?;
+        let (secs, micros) = time.split_once('.').unwrap_or((time, "0"));
+        let time = match (secs.parse(), format!("{:0<6}", micros).parse::<u32>()) {
+            (Ok(secs), Ok(micros)) if micros < 1_000_000 => Duration::new(secs, micros * 1000),
+            _ => return Err(ParseError::InvalidArgument(time.to_string())),
+        };
+
+        let event = parts.next().ok_or(ParseError::MissingArgument("event"))?.parse()?;
+
+        Ok(Self { seq, time, event })
+    }
+}
+
+/// Reads all events of an event log, skipping empty lines and comments
+pub fn read_log(log: impl BufRead) -> Result<Vec<LoggedEvent>, LogError> {
+    let mut events = Vec::new();
+
+    for (idx, line) in log.lines().enumerate() {
+        let line = line.map_err(|e| LogError::Read(e.to_string()))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        events.push(line.parse().map_err(|error| LogError::Parse { line: idx + 1, error })?);
+    }
+
+    Ok(events)
+}
+
+/// How the time between two events is chosen when replaying them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    AsFastAsPossible,
+    /// The recorded time between events, divided by `speed`
+    Original { speed: f64 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pacing {
+    pub timing: Timing,
+    /// Upper limit of events sent per second
+    pub max_rate: Option<f64>,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Self { timing: Timing::Original { speed: 1.0 }, max_rate: None }
+    }
+}
+
+impl Pacing {
+    /// Time to wait before sending an event that was recorded `gap` after the previous one
+    pub fn delay(&self, gap: Duration) -> Duration {
+        let delay = match self.timing {
+            Timing::AsFastAsPossible => Duration::ZERO,
+            Timing::Original { speed } => saturating_secs(gap.as_secs_f64() / speed),
+        };
+
+        match self.max_rate {
+            Some(rate) => delay.max(saturating_secs(1.0 / rate)),
+            None => delay,
+        }
+    }
+}
+
+/// `secs` as a duration, or the longest duration if it is too long for one (extreme speeds and rates are valid options)
+fn saturating_secs(secs: f64) -> Duration {
+    Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+}
+
+/// Sends all `events` to `sink`, waiting between them as given by `pacing`.
+///
+/// The gap before an event with sequence number 0 is ignored,
+/// as it is the first event of another session appended to the same log.
+pub fn replay(events: &[LoggedEvent], sink: &mut dyn EventSink, pacing: &Pacing) {
+    // when the previous event was sent, and when it was recorded
+    let mut previous: Option<(Instant, Duration)> = None;
+
+    for logged in events {
+        if let Some((sent, time)) = previous {
+            let gap = if logged.seq == 0 { Duration::ZERO } else { logged.time.saturating_sub(time) };
+            let delay = pacing.delay(gap);
+            let elapsed = sent.elapsed();
+            if delay > elapsed {
+                thread::sleep(delay - elapsed);
+            }
+        }
+
+        previous = Some((Instant::now(), logged.time));
+        logged.event.send_to(sink);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::{command::ParseError, Event, EventSink, PrintSink};
+
+    use super::{read_log, replay, LogError, LoggedEvent, Pacing, RecordingSink, Timing, LOG_HEADER};
+
+    #[test]
+    fn recording_sink_logs_and_forwards() {
+        let mut sink = RecordingSink::new(PrintSink::new(Vec::new()), Vec::new());
+        sink.pre_init(0);
+        sink.add_dependency(0, 1, 0xffffeeee, 0xffffeef);
+        sink.finish();
+
+        let (inner, log) = sink.into_inner();
+        let forwarded = String::from_utf8(inner.into_inner()).unwrap();
+        assert_eq!(forwarded.lines().count(), 3);
+
+        let log = String::from_utf8(log).unwrap();
+        let mut lines = log.lines();
+        assert_eq!(lines.next(), Some(LOG_HEADER));
+
+        for (seq, (line, expected)) in lines.zip(forwarded.lines()).enumerate() {
+            let mut parts = line.splitn(3, ' ');
+            assert_eq!(parts.next(), Some(seq.to_string().as_str()));
+
+            let (secs, micros) = parts.next().unwrap().split_once('.').unwrap();
+            assert!(secs.parse::<u64>().is_ok());
+            assert_eq!(micros.len(), 6);
+
+            assert_eq!(parts.next(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn read_log_of_recording() {
+        let mut sink = RecordingSink::new(PrintSink::new(Vec::new()), Vec::new());
+        sink.pre_init(0);
+        sink.init(2);
+        sink.run_task(3);
+
+        let (_, log) = sink.into_inner();
+        let events = read_log(log.as_slice()).unwrap();
+
+        let seqs: Vec<_> = events.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, [0, 1, 2]);
+
+        let events: Vec<_> = events.into_iter().map(|e| e.event).collect();
+        assert_eq!(events, [Event::PreInit { rt: 0 }, Event::Init { n_threads: 2 }, Event::RunTask { task_id: 3 }]);
+    }
+
+    #[test]
+    fn read_log_reports_line() {
+        let log = "# header\n0 1.5 ayu_event_preinit rt=0\n\n1 2.000001 ayu_event_jump\n";
+        assert_eq!(read_log(log.as_bytes()), Err(LogError::Parse { line: 4, error: ParseError::UnknownCommand("ayu_event_jump".to_string()) }));
+    }
+
+    #[test]
+    fn logged_event_parse() {
+        let event: LoggedEvent = "7 1697040000.5 ayu_event_barrier".parse().unwrap();
+        assert_eq!(event, LoggedEvent { seq: 7, time: Duration::new(1697040000, 500_000_000), event: Event::Barrier });
+
+        assert_eq!("7".parse::<LoggedEvent>(), Err(ParseError::MissingArgument("time")));
+        assert_eq!("x 1.0 ayu_event_barrier".parse::<LoggedEvent>(), Err(ParseError::InvalidArgument("x".to_string())));
+    }
+
+    #[test]
+    fn pacing_delay() {
+        let gap = Duration::from_millis(100);
+
+        let fast = Pacing { timing: Timing::AsFastAsPossible, max_rate: None };
+        assert_eq!(fast.delay(gap), Duration::ZERO);
+
+        assert_eq!(Pacing::default().delay(gap), gap);
+
+        let double = Pacing { timing: Timing::Original { speed: 2.0 }, max_rate: None };
+        assert_eq!(double.delay(gap), Duration::from_millis(50));
+
+        let capped = Pacing { timing: Timing::AsFastAsPossible, max_rate: Some(4.0) };
+        assert_eq!(capped.delay(gap), Duration::from_millis(250));
+        let capped = Pacing { timing: Timing::Original { speed: 1.0 }, max_rate: Some(100.0) };
+        assert_eq!(capped.delay(gap), gap);
+    }
+
+    #[test]
+    fn pacing_delay_saturates() {
+        let gap = Duration::from_millis(100);
+
+        let crawl = Pacing { timing: Timing::Original { speed: 1e-300 }, max_rate: None };
+        assert_eq!(crawl.delay(gap), Duration::MAX);
+        let trickle = Pacing { timing: Timing::AsFastAsPossible, max_rate: Some(1e-300) };
+        assert_eq!(trickle.delay(gap), Duration::MAX);
+        let rush = Pacing { timing: Timing::Original { speed: 1e300 }, max_rate: Some(1e300) };
+        assert_eq!(rush.delay(gap), Duration::ZERO);
+    }
+
+    #[test]
+    fn replay_sends_all_events() {
+        let log = "\
+0 100.0 ayu_event_preinit rt=0
+1 100.01 ayu_event_init n_threads=2
+0 5000.0 ayu_event_preinit rt=1
+";
+        let events = read_log(log.as_bytes()).unwrap();
+        let mut sink = PrintSink::new(Vec::new());
+
+        // the second session starts long after the first, which must not be waited for
+        replay(&events, &mut sink, &Pacing::default());
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output, "ayu_event_preinit rt=0\nayu_event_init n_threads=2\nayu_event_preinit rt=1\n");
+    }
+}
@@ -0,0 +1,236 @@
+use std::{
+    cell::RefCell,
+    ffi::CStr,
+    io::{self, Stdout, Write},
+    rc::Rc,
+    sync::Arc,
+};
+
+use crate::Event;
+
+/// A backend that Ayudame events are sent to.
+///
+/// There is one method per `ayu_event_*` function and the arguments are
+/// exactly the ones passed over FFI, so an implementation can forward them
+/// to libayudame, print them, record them, or just count them.
+pub trait EventSink {
+    fn pre_init(&mut self, rt: u64);
+    fn init(&mut self, n_threads: u64);
+    fn add_task(&mut self, task_id: u64, func_id: u64, priority: u64, scope_id: u64);
+    fn register_function(&mut self, func_id: u64, name: &CStr);
+    fn add_dependency(&mut self, to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64);
+    fn add_task_to_queue(&mut self, task_id: u64, thread_id: u64);
+    fn pre_run_task(&mut self, task_id: u64, thread_id: u64);
+    fn run_task(&mut self, task_id: u64);
+    fn post_run_task(&mut self, task_id: u64);
+    fn remove_task(&mut self, task_id: u64);
+    fn barrier(&mut self);
+    fn wait_on(&mut self, task_id: u64);
+    fn finish(&mut self);
+
+    /// A sink for the lifecycle events that goes to the same place as this one, but can be called from
+    /// several threads at once. Sinks that have to see all events in order, like the event log, return `None`.
+    fn sync_sink(&self) -> Option<Arc<dyn SyncEventSink>> {
+        None
+    }
+}
+
+/// Receives the lifecycle events of tasks from several threads at once,
+/// like a runtime calls them from the worker threads running the tasks
+pub trait SyncEventSink: Send + Sync {
+    fn add_task_to_queue(&self, task_id: u64, thread_id: u64);
+    fn pre_run_task(&self, task_id: u64, thread_id: u64);
+    fn run_task(&self, task_id: u64);
+    fn post_run_task(&self, task_id: u64);
+    fn remove_task(&self, task_id: u64);
+}
+
+/// Writes every event in the format of `Display for Event`, one per line,
+/// instead of sending it to ayudame
+pub struct PrintSink<W: Write> {
+    writer: W,
+}
+
+impl PrintSink<Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write> PrintSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn print(&mut self, event: Event) {
+        let _ = writeln!(self.writer, "{}", event);
+        let _ = self.writer.flush();
+    }
+}
+
+impl<W: Write> EventSink for PrintSink<W> {
+    impl_event_sink_via!(print);
+}
+
+impl<S: EventSink + ?Sized> EventSink for Box<S> {
+    fn pre_init(&mut self, rt: u64) {
+        (**self).pre_init(rt)
+    }
+
+    fn init(&mut self, n_threads: u64) {
+        (**self).init(n_threads)
+    }
+
+    fn add_task(&mut self, task_id: u64, func_id: u64, priority: u64, scope_id: u64) {
+        (**self).add_task(task_id, func_id, priority, scope_id)
+    }
+
+    fn register_function(&mut self, func_id: u64, name: &CStr) {
+        (**self).register_function(func_id, name)
+    }
+
+    fn add_dependency(&mut self, to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64) {
+        (**self).add_dependency(to_id, from_id, memaddr, orig_memaddr)
+    }
+
+    fn add_task_to_queue(&mut self, task_id: u64, thread_id: u64) {
+        (**self).add_task_to_queue(task_id, thread_id)
+    }
+
+    fn pre_run_task(&mut self, task_id: u64, thread_id: u64) {
+        (**self).pre_run_task(task_id, thread_id)
+    }
+
+    fn run_task(&mut self, task_id: u64) {
+        (**self).run_task(task_id)
+    }
+
+    fn post_run_task(&mut self, task_id: u64) {
+        (**self).post_run_task(task_id)
+    }
+
+    fn remove_task(&mut self, task_id: u64) {
+        (**self).remove_task(task_id)
+    }
+
+    fn barrier(&mut self) {
+        (**self).barrier()
+    }
+
+    fn wait_on(&mut self, task_id: u64) {
+        (**self).wait_on(task_id)
+    }
+
+    fn finish(&mut self) {
+        (**self).finish()
+    }
+
+    fn sync_sink(&self) -> Option<Arc<dyn SyncEventSink>> {
+        (**self).sync_sink()
+    }
+}
+
+/// Lets several owners send events to the same sink, one at a time
+impl<S: EventSink + ?Sized> EventSink for Rc<RefCell<S>> {
+    fn pre_init(&mut self, rt: u64) {
+        self.borrow_mut().pre_init(rt)
+    }
+
+    fn init(&mut self, n_threads: u64) {
+        self.borrow_mut().init(n_threads)
+    }
+
+    fn add_task(&mut self, task_id: u64, func_id: u64, priority: u64, scope_id: u64) {
+        self.borrow_mut().add_task(task_id, func_id, priority, scope_id)
+    }
+
+    fn register_function(&mut self, func_id: u64, name: &CStr) {
+        self.borrow_mut().register_function(func_id, name)
+    }
+
+    fn add_dependency(&mut self, to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64) {
+        self.borrow_mut().add_dependency(to_id, from_id, memaddr, orig_memaddr)
+    }
+
+    fn add_task_to_queue(&mut self, task_id: u64, thread_id: u64) {
+        self.borrow_mut().add_task_to_queue(task_id, thread_id)
+    }
+
+    fn pre_run_task(&mut self, task_id: u64, thread_id: u64) {
+        self.borrow_mut().pre_run_task(task_id, thread_id)
+    }
+
+    fn run_task(&mut self, task_id: u64) {
+        self.borrow_mut().run_task(task_id)
+    }
+
+    fn post_run_task(&mut self, task_id: u64) {
+        self.borrow_mut().post_run_task(task_id)
+    }
+
+    fn remove_task(&mut self, task_id: u64) {
+        self.borrow_mut().remove_task(task_id)
+    }
+
+    fn barrier(&mut self) {
+        self.borrow_mut().barrier()
+    }
+
+    fn wait_on(&mut self, task_id: u64) {
+        self.borrow_mut().wait_on(task_id)
+    }
+
+    fn finish(&mut self) {
+        self.borrow_mut().finish()
+    }
+
+    fn sync_sink(&self) -> Option<Arc<dyn SyncEventSink>> {
+        self.borrow().sync_sink()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, ffi::CString, rc::Rc};
+
+    use super::{EventSink, PrintSink};
+
+    #[test]
+    fn print_sink_writes_one_line_per_event() {
+        let mut sink = PrintSink::new(Vec::new());
+        sink.pre_init(0);
+        sink.init(2);
+        sink.register_function(0, &CString::new("f").unwrap());
+        sink.add_task(0, 0, 1, 1);
+        sink.run_task(0);
+        sink.finish();
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output, "\
+ayu_event_preinit rt=0
+ayu_event_init n_threads=2
+ayu_event_registerfunction func_id=0 name=\"f\"
+ayu_event_addtask task_id=0 func_id=0 priority=1 scope_id=1
+ayu_event_runtask task_id=0
+ayu_event_finish
+");
+    }
+
+    #[test]
+    fn shared_sink_forwards_to_inner() {
+        let sink = Rc::new(RefCell::new(PrintSink::new(Vec::new())));
+        let mut first = Rc::clone(&sink);
+        let mut second = Rc::clone(&sink);
+        first.barrier();
+        second.wait_on(3);
+
+        drop((first, second));
+        let inner = Rc::try_unwrap(sink).ok().unwrap().into_inner();
+        let output = String::from_utf8(inner.into_inner()).unwrap();
+        assert_eq!(output, "ayu_event_barrier\nayu_event_waiton task_id=3\n");
+    }
+}
@@ -0,0 +1,552 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    hint, io,
+    str::FromStr,
+    sync::{mpsc::{self, Sender}, Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    command::ParseError,
+    simulator::{advance, PendingTasks},
+    AppState, Durations, Event, EventSink, ReadyTask, Scheduler, SyncEventSink, TaskState,
+};
+
+/// The most worker threads an execution starts. With more threads, every worker runs the tasks of several of them.
+pub const MAX_WORKERS: u64 = 256;
+
+/// How tasks are run on the worker threads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execution {
+    /// How long tasks run, in ticks
+    pub durations: Durations,
+    /// The wall clock time of a tick
+    pub tick: Duration,
+    /// Whether the workers spin while running a task, instead of sleeping
+    pub spin: bool,
+}
+
+impl Default for Execution {
+    fn default() -> Self {
+        Self { durations: Durations::default(), tick: Duration::from_millis(10), spin: false }
+    }
+}
+
+/// Parses `[tick=<ms>] [spin]`, followed by the durations of the tasks like `Durations`
+impl FromStr for Execution {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut execution = Execution::default();
+        let mut durations = Vec::new();
+
+        for word in s.split_whitespace() {
+            if word == "spin" {
+                execution.spin = true;
+            } else if let Some(ms) = word.strip_prefix("tick=") {
+                execution.tick = Duration::from_millis(ms.parse().map_err(|_| ParseError::InvalidArgument(word.to_string()))?);
+            } else {
+                durations.push(word);
+            }
+        }
+        execution.durations = durations.join(" ").parse()?;
+
+        Ok(execution)
+    }
+}
+
+/// The outcome of an execution
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// Number of tasks that ran
+    pub tasks: usize,
+    /// Wall clock time from starting the workers until the last of them stopped
+    pub elapsed: Duration,
+    /// Time each worker spent running tasks and sending their events, there are at most `MAX_WORKERS` of them
+    pub busy: Vec<Duration>,
+    /// Tasks that never became ready, because they depend on each other in a cycle
+    pub blocked: Vec<u64>,
+}
+
+impl ExecutionReport {
+    /// The share of the elapsed time each worker spent running tasks
+    pub fn utilisation(&self) -> Vec<f64> {
+        self.busy.iter().map(|busy| if self.elapsed.is_zero() { 0.0 } else { busy.as_secs_f64() / self.elapsed.as_secs_f64() }).collect()
+    }
+}
+
+impl Display for ExecutionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Executed {} tasks on {} workers in {:.1} ms", self.tasks, self.busy.len(), millis(self.elapsed))?;
+        for (worker, (busy, utilisation)) in self.busy.iter().zip(self.utilisation()).enumerate() {
+            write!(f, "\n\tWorker {}: busy for {:.1} ms, utilisation {:.1}%", worker, millis(*busy), utilisation * 100.0)?;
+        }
+        if !self.blocked.is_empty() {
+            let ids: Vec<_> = self.blocked.iter().map(|id| id.to_string()).collect();
+            write!(f, "\n\tTasks {} never became ready.", ids.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// Runs all unfinished tasks in `state` on a worker thread per thread of the runtime, which send the lifecycle events
+/// of the tasks they run. The tasks are handed to `scheduler` like in `simulate`, and every task is removed right after it has run.
+/// Beyond `MAX_WORKERS` threads, worker `w` runs the tasks of all threads `t` with `t % MAX_WORKERS == w`.
+///
+/// If `sink` has a `sync_sink`, the workers call it directly. Otherwise they pass their events to this thread,
+/// which sends them to `sink` in the order they were sent.
+/// Fails if not all workers can be started, in which case no event is sent and no task is run.
+pub fn execute(state: &mut AppState, sink: &mut dyn EventSink, execution: &Execution, mut scheduler: Box<dyn Scheduler>) -> io::Result<ExecutionReport> {
+    let PendingTasks { task_ids, children, waiting_for, ready } = PendingTasks::of(state);
+    // the ready tasks only move on in `state` once all workers have started
+    let ready: Vec<_> = ready.into_iter().map(|id| {
+        let task = state.get_task(id).expect("unfinished tasks exist");
+        (id, scheduler.push(task.as_ref().into(), task.into_raw_parts().3))
+    }).collect();
+
+    // the workers cannot touch the tasks in `state`, so they get a copy of everything they need
+    let tasks = task_ids.iter().map(|&id| {
+        let task = state.get_task(id).expect("unfinished tasks exist");
+        let ticks = u32::try_from(execution.durations.of(task)).unwrap_or(u32::MAX);
+        let pending = PendingTask {
+            ready: task.as_ref().into(),
+            // tasks that become ready later are queued by the worker releasing them, the others before the workers start
+            state: task.get_state().max(TaskState::Queued),
+            duration: execution.tick.checked_mul(ticks).unwrap_or(Duration::MAX),
+        };
+        (id, pending)
+    }).collect();
+    let n_threads = state.n_threads.max(1);
+    let graph = SharedGraph {
+        state: Mutex::new(GraphState { scheduler, waiting_for, queued: ready.len(), running: 0, finished: Vec::new(), aborted: false }),
+        changed: Condvar::new(),
+        tasks,
+        children,
+        n_threads,
+        n_workers: n_threads.min(MAX_WORKERS),
+        spin: execution.spin,
+    };
+
+    let queue_ready = |sink: &mut dyn EventSink| for &(id, thread_id) in &ready {
+        advance(state, sink, id, thread_id, TaskState::Queued);
+    };
+    let start = Instant::now();
+    let busy = match sink.sync_sink() {
+        Some(sync) => graph.run(sink, sync, queue_ready, |_| ()),
+        None => {
+            let (sender, receiver) = mpsc::channel();
+            graph.run(sink, Arc::new(Forward(sender)), queue_ready, |sink| receiver.into_iter().for_each(|event| event.send_to(sink)))
+        },
+    }?;
+    let elapsed = start.elapsed();
+
+    let finished = graph.state.into_inner().expect("workers do not panic").finished;
+    for &id in &finished {
+        state.force_task_state(id, TaskState::Removed);
+    }
+
+    let blocked = task_ids.into_iter().filter(|id| state.does_task_exist(*id)).collect();
+    Ok(ExecutionReport { tasks: finished.len(), elapsed, busy, blocked })
+}
+
+/// What a worker needs to know to run a task
+struct PendingTask {
+    ready: ReadyTask,
+    /// The state the task is in when a worker takes it
+    state: TaskState,
+    duration: Duration,
+}
+
+/// The parts of the graph that change while it is executed
+struct GraphState {
+    scheduler: Box<dyn Scheduler>,
+    waiting_for: HashMap<u64, usize>,
+    /// Number of tasks that were handed to the scheduler, but not taken by a worker yet
+    queued: usize,
+    running: usize,
+    finished: Vec<u64>,
+    /// Set if not all workers could be started, the others stop right away
+    aborted: bool,
+}
+
+/// The task graph as the workers see it. Workers take tasks and release their children while holding the lock on `state`,
+/// and wait for `changed` when there is no task for them.
+struct SharedGraph {
+    state: Mutex<GraphState>,
+    changed: Condvar,
+    tasks: HashMap<u64, PendingTask>,
+    children: HashMap<u64, Vec<u64>>,
+    /// The number of threads of the runtime
+    n_threads: u64,
+    n_workers: u64,
+    spin: bool,
+}
+
+impl SharedGraph {
+    /// Runs the graph on the workers sending their events to `workers_sink`. Once all of them have started, calls `start`
+    /// before they take any task, and then `meanwhile`, both on this thread with `sink`. Returns the time each worker was busy.
+    fn run(
+        &self,
+        sink: &mut dyn EventSink,
+        workers_sink: Arc<dyn SyncEventSink>,
+        start: impl FnOnce(&mut dyn EventSink),
+        meanwhile: impl FnOnce(&mut dyn EventSink),
+    ) -> io::Result<Vec<Duration>> {
+        thread::scope(|scope| {
+            // the workers wait for the lock until all of them have been started
+            let mut state = self.state.lock().expect("workers do not panic");
+            let mut workers = Vec::with_capacity(self.n_workers as usize);
+            let mut error = None;
+            for worker in 0..self.n_workers {
+                let sink = Arc::clone(&workers_sink);
+                let spawned = thread::Builder::new()
+                    .name(format!("worker {}", worker))
+                    .spawn_scoped(scope, move || self.work(worker, sink.as_ref()));
+                match spawned {
+                    Ok(handle) => workers.push(handle),
+                    Err(e) => {
+                        state.aborted = true;
+                        error = Some(e);
+                        break;
+                    },
+                }
+            }
+            if error.is_none() {
+                start(sink);
+            }
+            drop(state);
+            // only the workers hold on to their sink now, so forwarded events end when they do
+            drop(workers_sink);
+            meanwhile(sink);
+
+            let busy = workers.into_iter().map(|worker| worker.join().expect("workers do not panic")).collect();
+            error.map_or(Ok(busy), Err)
+        })
+    }
+
+    /// Takes tasks of the threads `worker` runs until none are queued or running, and returns the time spent running them
+    fn work(&self, worker: u64, sink: &dyn SyncEventSink) -> Duration {
+        let mut busy = Duration::ZERO;
+        let mut state = self.state.lock().expect("workers do not panic");
+
+        loop {
+            if state.aborted {
+                return busy;
+            }
+            let popped = (worker..self.n_threads)
+                .step_by(self.n_workers as usize)
+                .find_map(|thread_id| state.scheduler.pop(thread_id).map(|task_id| (task_id, thread_id)));
+            let Some((task_id, thread_id)) = popped else {
+                // no task can become ready anymore
+                if state.queued == 0 && state.running == 0 {
+                    self.changed.notify_all();
+                    return busy;
+                }
+                state = self.changed.wait(state).expect("workers do not panic");
+                continue;
+            };
+            state.queued -= 1;
+            state.running += 1;
+            drop(state);
+
+            let started = Instant::now();
+            let task = &self.tasks[&task_id];
+            send_lifecycle(sink, task_id, thread_id, task.state, TaskState::Running);
+            self.pass(task.duration);
+            send_lifecycle(sink, task_id, thread_id, TaskState::Running, TaskState::PostRun);
+            sink.remove_task(task_id);
+            busy += started.elapsed();
+
+            state = self.state.lock().expect("workers do not panic");
+            state.running -= 1;
+            state.finished.push(task_id);
+            for child in &self.children[&task_id] {
+                let Some(count) = state.waiting_for.get_mut(child) else {
+                    continue;
+                };
+                *count -= 1;
+                if *count == 0 {
+                    let queue_thread = state.scheduler.push(self.tasks[child].ready, thread_id);
+                    state.queued += 1;
+                    // still holding the lock, so no worker can run the child before it was queued
+                    sink.add_task_to_queue(*child, queue_thread);
+                }
+            }
+            self.changed.notify_all();
+        }
+    }
+
+    /// Lets `duration` pass, like a task doing its work
+    fn pass(&self, duration: Duration) {
+        if !self.spin {
+            thread::sleep(duration);
+            return;
+        }
+
+        let end = Instant::now() + duration;
+        while Instant::now() < end {
+            hint::spin_loop();
+        }
+    }
+}
+
+/// Sends the events moving a task from `from` on to `to`, like `advance` does for tasks in the state
+fn send_lifecycle(sink: &dyn SyncEventSink, task_id: u64, thread_id: u64, from: TaskState, to: TaskState) {
+    let mut state = from;
+    while state < to {
+        state = state.successors()[0];
+        match state {
+            TaskState::Queued => sink.add_task_to_queue(task_id, thread_id),
+            TaskState::PreRun => sink.pre_run_task(task_id, thread_id),
+            TaskState::Running => sink.run_task(task_id),
+            _ => sink.post_run_task(task_id),
+        }
+    }
+}
+
+/// Passes the events of the workers on to a sink that is not thread-safe
+struct Forward(Sender<Event>);
+
+impl Forward {
+    fn send(&self, event: Event) {
+        // the receiver only stops listening once all workers are done
+        let _ = self.0.send(event);
+    }
+}
+
+impl SyncEventSink for Forward {
+    fn add_task_to_queue(&self, task_id: u64, thread_id: u64) {
+        self.send(Event::AddTaskToQueue { task_id, thread_id })
+    }
+
+    fn pre_run_task(&self, task_id: u64, thread_id: u64) {
+        self.send(Event::PreRunTask { task_id, thread_id })
+    }
+
+    fn run_task(&self, task_id: u64) {
+        self.send(Event::RunTask { task_id })
+    }
+
+    fn post_run_task(&self, task_id: u64) {
+        self.send(Event::PostRunTask { task_id })
+    }
+
+    fn remove_task(&self, task_id: u64) {
+        self.send(Event::RemoveTask { task_id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Condvar, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use crate::{command::ParseError, AppState, Event, EventSink, PrintSink, SchedulingPolicy, SyncEventSink, TaskState};
+
+    use super::{execute, Execution, MAX_WORKERS};
+
+    /// Records every event together with the name of the thread that sent it
+    #[derive(Clone, Default)]
+    struct ThreadRecorder {
+        events: Arc<Mutex<Vec<(String, Event)>>>,
+        meeting: Arc<Meeting>,
+    }
+
+    /// Tasks whose `run_task` only returns once all of them have been run
+    #[derive(Default)]
+    struct Meeting {
+        tasks: Vec<u64>,
+        /// How many of them have been run so far
+        there: Mutex<usize>,
+        all_there: Condvar,
+    }
+
+    impl ThreadRecorder {
+        fn record(&self, event: Event) {
+            let thread = thread::current().name().unwrap_or_default().to_string();
+            self.events.lock().unwrap().push((thread, event));
+        }
+
+        /// Waits until all tasks of the meeting run, which they can only do at the same time
+        fn meet(&self, task_id: u64) {
+            let meeting = &*self.meeting;
+            if !meeting.tasks.contains(&task_id) {
+                return;
+            }
+
+            let mut there = meeting.there.lock().unwrap();
+            *there += 1;
+            meeting.all_there.notify_all();
+            let (_there, timeout) = meeting.all_there.wait_timeout_while(there, Duration::from_secs(10), |there| *there < meeting.tasks.len()).unwrap();
+            assert!(!timeout.timed_out(), "tasks {:?} did not run at the same time", meeting.tasks);
+        }
+
+        fn record_mut(&mut self, event: Event) {
+            self.record(event)
+        }
+
+        fn position(&self, event: &Event) -> usize {
+            self.events.lock().unwrap().iter().position(|(_, e)| e == event).unwrap()
+        }
+    }
+
+    impl EventSink for ThreadRecorder {
+        impl_event_sink_via!(record_mut);
+
+        fn sync_sink(&self) -> Option<Arc<dyn SyncEventSink>> {
+            Some(Arc::new(self.clone()))
+        }
+    }
+
+    impl SyncEventSink for ThreadRecorder {
+        fn add_task_to_queue(&self, task_id: u64, thread_id: u64) {
+            self.record(Event::AddTaskToQueue { task_id, thread_id })
+        }
+
+        fn pre_run_task(&self, task_id: u64, thread_id: u64) {
+            self.record(Event::PreRunTask { task_id, thread_id })
+        }
+
+        fn run_task(&self, task_id: u64) {
+            self.record(Event::RunTask { task_id });
+            self.meet(task_id);
+        }
+
+        fn post_run_task(&self, task_id: u64) {
+            self.record(Event::PostRunTask { task_id })
+        }
+
+        fn remove_task(&self, task_id: u64) {
+            self.record(Event::RemoveTask { task_id })
+        }
+    }
+
+    /// Tasks 0 to 3 on thread 0, where 1 and 2 depend on 0, and 3 on both of them
+    fn diamond(n_threads: u64) -> AppState {
+        let mut state = AppState::new();
+        state.n_threads = n_threads;
+        for _ in 0..4 {
+            state.create_task(false, None, 0).unwrap();
+        }
+        for (parent, child) in [(0, 1), (0, 2), (1, 3), (2, 3)] {
+            state.add_dependency(parent, child).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn execute_on_worker_threads() {
+        let mut state = diamond(2);
+        // tasks 1 and 2 can only finish running if they run at the same time, on both workers
+        let meeting = Meeting { tasks: vec![1, 2], ..Meeting::default() };
+        let mut sink = ThreadRecorder { meeting: Arc::new(meeting), ..ThreadRecorder::default() };
+        let execution = "tick=1 duration=5".parse().unwrap();
+        let report = execute(&mut state, &mut sink, &execution, SchedulingPolicy::Fifo.scheduler(2)).unwrap();
+
+        assert_eq!(report.tasks, 4);
+        assert!(report.elapsed >= Duration::from_millis(15));
+        assert!(state.task_ids().is_empty());
+
+        let events = sink.events.lock().unwrap().clone();
+        assert_eq!(events.len(), 4 * 5);
+        // only task 0 is queued before the workers start, all other events come from the worker running the task
+        assert!(events[1..].iter().all(|(thread, _)| thread.starts_with("worker ")));
+        for (thread, event) in &events {
+            if let Event::PreRunTask { thread_id, .. } = event {
+                assert_eq!(*thread, format!("worker {}", thread_id));
+            }
+        }
+
+        for (parent, child) in [(0, 1), (0, 2), (1, 3), (2, 3)] {
+            let parent_done = sink.position(&Event::PostRunTask { task_id: parent });
+            let child_queued = events.iter().position(|(_, e)| matches!(e, Event::AddTaskToQueue { task_id, .. } if *task_id == child)).unwrap();
+            assert!(parent_done < child_queued, "task {} was queued before task {} finished", child, parent);
+        }
+    }
+
+    #[test]
+    fn execute_forwards_to_other_sinks() {
+        let mut state = diamond(1);
+        // task 0 has started already, and task 4 depends on itself through task 5
+        state.transition_task(0, TaskState::Queued).unwrap();
+        state.transition_task(0, TaskState::PreRun).unwrap();
+        state.allow_cycles = true;
+        for _ in 0..2 {
+            state.create_task(false, None, 0).unwrap();
+        }
+        state.add_dependency(4, 5).unwrap();
+        state.add_dependency(5, 4).unwrap();
+
+        let mut sink = PrintSink::new(Vec::new());
+        let execution = Execution { spin: true, tick: Duration::ZERO, ..Execution::default() };
+        let report = execute(&mut state, &mut sink, &execution, SchedulingPolicy::Lifo.scheduler(1)).unwrap();
+
+        assert_eq!(String::from_utf8(sink.into_inner()).unwrap(), "\
+ayu_event_runtask task_id=0
+ayu_event_postruntask task_id=0
+ayu_event_removetask task_id=0
+ayu_event_addtasktoqueue task_id=1 thread_id=0
+ayu_event_addtasktoqueue task_id=2 thread_id=0
+ayu_event_preruntask task_id=2 thread_id=0
+ayu_event_runtask task_id=2
+ayu_event_postruntask task_id=2
+ayu_event_removetask task_id=2
+ayu_event_preruntask task_id=1 thread_id=0
+ayu_event_runtask task_id=1
+ayu_event_postruntask task_id=1
+ayu_event_removetask task_id=1
+ayu_event_addtasktoqueue task_id=3 thread_id=0
+ayu_event_preruntask task_id=3 thread_id=0
+ayu_event_runtask task_id=3
+ayu_event_postruntask task_id=3
+ayu_event_removetask task_id=3
+");
+        assert_eq!(report.tasks, 4);
+        assert_eq!(report.blocked, [4, 5]);
+        assert!(report.to_string().starts_with("Executed 4 tasks on 1 workers in "));
+        assert!(report.to_string().ends_with("\n\tTasks 4, 5 never became ready."));
+    }
+
+    #[test]
+    fn execute_shares_workers_between_threads() {
+        let mut state = AppState::new();
+        state.n_threads = MAX_WORKERS + 2;
+        for thread_id in [0, 1, MAX_WORKERS, MAX_WORKERS + 1] {
+            state.create_task(false, None, thread_id).unwrap();
+        }
+
+        let mut sink = ThreadRecorder::default();
+        let execution = Execution { tick: Duration::ZERO, ..Execution::default() };
+        let report = execute(&mut state, &mut sink, &execution, SchedulingPolicy::Affinity.scheduler(MAX_WORKERS + 2)).unwrap();
+        assert_eq!(report.tasks, 4);
+        assert_eq!(report.busy.len() as u64, MAX_WORKERS);
+
+        // the tasks keep their threads, but the last two run on the workers of the first two
+        let events = sink.events.lock().unwrap().clone();
+        for (task_id, thread_id) in [0, 1, MAX_WORKERS, MAX_WORKERS + 1].into_iter().enumerate() {
+            let (worker, _) = events.iter().find(|(_, e)| *e == Event::PreRunTask { task_id: task_id as u64, thread_id }).unwrap();
+            assert_eq!(*worker, format!("worker {}", thread_id % MAX_WORKERS));
+        }
+    }
+
+    #[test]
+    fn execution_parse() {
+        let execution: Execution = "tick=2 spin GEMM=3".parse().unwrap();
+        assert_eq!(execution.tick, Duration::from_millis(2));
+        assert!(execution.spin);
+        assert_eq!(execution.durations.labels.get("GEMM"), Some(&3));
+
+        assert_eq!("".parse(), Ok(Execution::default()));
+        assert_eq!("tick=fast".parse::<Execution>(), Err(ParseError::InvalidArgument("tick=fast".to_string())));
+        assert_eq!("spin 2".parse::<Execution>(), Err(ParseError::UnexpectedArgument("2".to_string())));
+    }
+}
@@ -0,0 +1,323 @@
+use std::{collections::{BTreeSet, HashMap}, fmt::Display, hash::Hash, str::FromStr};
+
+use crate::{command::ParseError, dependency::memory_addresses, AppState, EventSink};
+
+pub mod dense;
+pub mod patterns;
+pub mod random;
+pub mod sparse;
+
+pub use dense::{DenseFactorization, Factorization};
+pub use patterns::Pattern;
+pub use random::{RandomGraph, RandomShape};
+pub use sparse::{read_matrix_market, EliminationOrder, MatrixError, SparseMatrix, SymbolicFactorization};
+
+/// The most tasks a generated graph may have, larger ones are refused when parsing their parameters
+pub const MAX_TASKS: usize = 1_000_000;
+
+/// A task graph that is yet to be created
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphSpec {
+    /// Names of the labels, registered as new functions
+    pub labels: Vec<String>,
+    pub tasks: Vec<TaskSpec>,
+    /// Dependencies as indices into `tasks`, where the parent always comes first
+    pub dependencies: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskSpec {
+    /// Index into the labels of the graph
+    pub label: usize,
+    pub is_critical: bool,
+}
+
+impl GraphSpec {
+    /// Registers the labels, then creates all tasks and dependencies in `state`, sending the events for them to `sink`.
+    /// The tasks are spread over the threads of the runtime round robin.
+    /// Returns the ids of the created tasks, or `TaskIdsExhausted` without creating anything if there are not enough ids left for them.
+    pub fn build(&self, state: &mut AppState, sink: &mut dyn EventSink) -> Result<Vec<u64>, TaskIdsExhausted> {
+        if (self.tasks.len() as u64) > state.remaining_task_ids() {
+            return Err(TaskIdsExhausted { tasks: self.tasks.len() });
+        }
+
+        let function_ids: Vec<_> = self.labels.iter()
+            .map(|name| {
+                let function = state.create_function(name.clone()).expect("labels of generated graphs are ASCII");
+                sink.register_function(function.id, function.c_name());
+                function.id
+            })
+            .collect();
+
+        let task_ids: Vec<_> = self.tasks.iter().enumerate()
+            .map(|(idx, spec)| {
+                let thread_id = idx as u64 % state.n_threads;
+                let task = state.create_task(spec.is_critical, Some(function_ids[spec.label]), thread_id).expect("there are enough ids and the thread ids are in range");
+                let (task_id, func_id, priority, scope_id) = task.into_raw_parts();
+                sink.add_task(task_id, func_id, priority, scope_id);
+                task_id
+            })
+            .collect();
+
+        for &(parent, child) in &self.dependencies {
+            let (parent_id, child_id) = (task_ids[parent], task_ids[child]);
+            // parents come first, so the dependencies of the new tasks cannot form a cycle
+            if state.add_acyclic_dependency(parent_id, child_id).is_ok() {
+                let (memaddr, orig_memaddr) = memory_addresses(parent_id, child_id);
+                sink.add_dependency(parent_id, child_id, memaddr, orig_memaddr);
+            }
+        }
+
+        Ok(task_ids)
+    }
+}
+
+/// There are not enough task ids left to create the tasks of a graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskIdsExhausted {
+    pub tasks: usize,
+}
+
+impl Display for TaskIdsExhausted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "There are not enough task ids left for {} tasks.", self.tasks)
+    }
+}
+
+impl std::error::Error for TaskIdsExhausted { }
+
+/// Builds a graph in program order, deriving the dependencies of each task from the data it accesses,
+/// like a task-based runtime does: a task depends on the last task writing the data it reads or writes,
+/// and a task writing data also depends on all tasks that read it since it was last written.
+pub(crate) struct GraphBuilder<K> {
+    spec: GraphSpec,
+    dependencies: BTreeSet<(usize, usize)>,
+    last_writer: HashMap<K, usize>,
+    readers: HashMap<K, Vec<usize>>,
+}
+
+impl<K: Hash + Eq + Clone> GraphBuilder<K> {
+    pub(crate) fn new() -> Self {
+        Self { spec: GraphSpec::default(), dependencies: BTreeSet::new(), last_writer: HashMap::new(), readers: HashMap::new() }
+    }
+
+    /// Adds a task reading `reads` and writing `writes`, and returns its index.
+    /// The label is added to the graph when it is used for the first time.
+    pub(crate) fn task(&mut self, label: &str, is_critical: bool, reads: &[K], writes: &[K]) -> usize {
+        let label = match self.spec.labels.iter().position(|l| l == label) {
+            Some(idx) => idx,
+            None => {
+                self.spec.labels.push(label.to_string());
+                self.spec.labels.len() - 1
+            },
+        };
+        let task = self.spec.tasks.len();
+        self.spec.tasks.push(TaskSpec { label, is_critical });
+
+        for data in reads {
+            if let Some(&writer) = self.last_writer.get(data) {
+                self.dependencies.insert((writer, task));
+            }
+            self.readers.entry(data.clone()).or_default().push(task);
+        }
+
+        for data in writes {
+            if let Some(&writer) = self.last_writer.get(data) {
+                self.dependencies.insert((writer, task));
+            }
+            for reader in self.readers.remove(data).unwrap_or_default() {
+                if reader != task {
+                    self.dependencies.insert((reader, task));
+                }
+            }
+            self.last_writer.insert(data.clone(), task);
+        }
+
+        task
+    }
+
+    /// Adds a task depending on `parents`, regardless of any data, and returns its index
+    pub(crate) fn task_after(&mut self, label: &str, parents: &[usize]) -> usize {
+        let task = self.task(label, false, &[], &[]);
+        self.dependencies.extend(parents.iter().map(|&parent| (parent, task)));
+        task
+    }
+
+    pub(crate) fn finish(self) -> GraphSpec {
+        GraphSpec { dependencies: self.dependencies.into_iter().collect(), ..self.spec }
+    }
+}
+
+/// A kind of graph that can be generated, together with its parameters
+#[derive(Debug, Clone, PartialEq)]
+pub enum Generator {
+    Random(RandomGraph),
+    Dense(DenseFactorization),
+    Pattern(Pattern),
+}
+
+impl Generator {
+    pub fn generate(&self) -> GraphSpec {
+        match self {
+            Generator::Random(graph) => graph.generate(),
+            Generator::Dense(factorization) => factorization.generate(),
+            Generator::Pattern(pattern) => pattern.generate(),
+        }
+    }
+}
+
+/// Parses `<shape> [<parameter>=<value> ...]`, as in `layered tasks=100 layers=10 seed=3`
+impl FromStr for Generator {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let shape = words.next().ok_or(ParseError::MissingArgument("shape"))?;
+        let mut params = Parameters::new(words)?;
+
+        let generator = match shape {
+            "layered" | "random" | "bounded" => Generator::Random(RandomGraph::from_parameters(shape, &mut params)?),
+            "cholesky" | "lu" | "qr" => Generator::Dense(DenseFactorization::from_parameters(shape, &mut params)?),
+            "forkjoin" | "pipeline" | "wavefront" | "stencil" | "mapreduce" => Generator::Pattern(Pattern::from_parameters(shape, &mut params)?),
+            _ => return Err(ParseError::InvalidArgument(shape.to_string())),
+        };
+
+        params.finish()?;
+        Ok(generator)
+    }
+}
+
+/// The `<parameter>=<value>` arguments of a generator
+pub(crate) struct Parameters {
+    params: Vec<(String, String)>,
+}
+
+impl Parameters {
+    fn new<'a>(words: impl Iterator<Item = &'a str>) -> Result<Self, ParseError> {
+        let params = words
+            .map(|word| match word.split_once('=') {
+                Some((key, value)) => Ok((key.to_string(), value.to_string())),
+                None => Err(ParseError::UnexpectedArgument(word.to_string())),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { params })
+    }
+
+    /// Removes and parses the value of `key`, returning `default` if it is not given
+    pub(crate) fn take<T: FromStr>(&mut self, key: &str, default: T) -> Result<T, ParseError> {
+        self.take_if(key, default, |_| true)
+    }
+
+    /// Like `take`, but the value also has to be `valid`
+    pub(crate) fn take_if<T: FromStr>(&mut self, key: &str, default: T, valid: impl Fn(&T) -> bool) -> Result<T, ParseError> {
+        let Some(idx) = self.params.iter().position(|(k, _)| k == key) else {
+            return Ok(default);
+        };
+
+        let (key, value) = self.params.remove(idx);
+        match value.parse() {
+            Ok(value) if valid(&value) => Ok(value),
+            _ => Err(ParseError::InvalidArgument(format!("{}={}", key, value))),
+        }
+    }
+
+    /// Fails if there are parameters left that no generator took
+    fn finish(self) -> Result<(), ParseError> {
+        match self.params.into_iter().next() {
+            Some((key, value)) => Err(ParseError::UnexpectedArgument(format!("{}={}", key, value))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::ParseError, AppState, PrintSink};
+
+    use super::{Generator, GraphBuilder, GraphSpec, Pattern, RandomGraph, RandomShape, TaskIdsExhausted, TaskSpec};
+
+    #[test]
+    fn build_sends_events() {
+        let spec = GraphSpec {
+            labels: vec!["a".to_string(), "b".to_string()],
+            tasks: vec![TaskSpec { label: 0, is_critical: true }, TaskSpec { label: 1, is_critical: false }, TaskSpec { label: 1, is_critical: false }],
+            dependencies: vec![(0, 1), (0, 2)],
+        };
+
+        let mut state = AppState::new();
+        state.create_task(false, None, 0).unwrap();
+        let mut sink = PrintSink::new(Vec::new());
+
+        assert_eq!(spec.build(&mut state, &mut sink), Ok(vec![1, 2, 3]));
+        assert_eq!(state.get_children(1), Some(vec![2, 3]));
+
+        let events = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(events, "\
+ayu_event_registerfunction func_id=0 name=\"a\"
+ayu_event_registerfunction func_id=1 name=\"b\"
+ayu_event_addtask task_id=1 func_id=0 priority=1 scope_id=0
+ayu_event_addtask task_id=2 func_id=1 priority=0 scope_id=1
+ayu_event_addtask task_id=3 func_id=1 priority=0 scope_id=0
+ayu_event_adddependency to_id=1 from_id=2 memaddr=0xffffeeef orig_memaddr=0xffffeee
+ayu_event_adddependency to_id=1 from_id=3 memaddr=0xffffeeef orig_memaddr=0xffffeef
+");
+    }
+
+    #[test]
+    fn build_needs_enough_task_ids() {
+        let spec = GraphSpec { labels: vec!["a".to_string()], tasks: vec![TaskSpec { label: 0, is_critical: false }; 2], dependencies: vec![(0, 1)] };
+
+        let mut state = AppState::new();
+        state.create_task_with_id(u64::MAX - 1, false, None, 0).unwrap();
+        let mut sink = PrintSink::new(Vec::new());
+
+        assert_eq!(spec.build(&mut state, &mut sink), Err(TaskIdsExhausted { tasks: 2 }));
+        assert!(sink.into_inner().is_empty());
+        assert_eq!(state.task_ids(), [u64::MAX - 1]);
+    }
+
+    #[test]
+    fn builder_follows_data_flow() {
+        let mut builder = GraphBuilder::new();
+        let write_a = builder.task("write", false, &[], &["a"]);
+        let read_a = builder.task("read", false, &["a"], &["b"]);
+        let read_a_again = builder.task("read", false, &["a"], &[]);
+        let update_a = builder.task("update", true, &["a"], &["a"]);
+        let read_b = builder.task("read", false, &["b"], &[]);
+
+        let spec = builder.finish();
+        assert_eq!(spec.labels, ["write", "read", "update"]);
+        assert_eq!(spec.tasks[update_a], TaskSpec { label: 2, is_critical: true });
+        assert_eq!(spec.dependencies, [
+            (write_a, read_a),
+            (write_a, read_a_again),
+            (write_a, update_a),
+            (read_a, update_a),
+            (read_a, read_b),
+            (read_a_again, update_a),
+        ]);
+    }
+
+    #[test]
+    fn generator_parse() {
+        let generator: Generator = "layered tasks=30 layers=5 density=0.5 seed=9".parse().unwrap();
+        assert_eq!(generator, Generator::Random(RandomGraph {
+            shape: RandomShape::Layered { layers: 5 },
+            tasks: 30,
+            density: 0.5,
+            seed: 9,
+            ..RandomGraph::default()
+        }));
+
+        let generator: Generator = "stencil width=8 iterations=10".parse().unwrap();
+        assert_eq!(generator, Generator::Pattern(Pattern::Stencil { width: 8, depth: 4, iterations: 10 }));
+        assert_eq!("mapreduce depth=2".parse::<Generator>(), Err(ParseError::UnexpectedArgument("depth=2".to_string())));
+
+        assert_eq!("".parse::<Generator>(), Err(ParseError::MissingArgument("shape")));
+        assert_eq!("spiral".parse::<Generator>(), Err(ParseError::InvalidArgument("spiral".to_string())));
+        assert_eq!("random density=2".parse::<Generator>(), Err(ParseError::InvalidArgument("density=2".to_string())));
+        assert_eq!("random layers=2".parse::<Generator>(), Err(ParseError::UnexpectedArgument("layers=2".to_string())));
+        assert_eq!("random 12".parse::<Generator>(), Err(ParseError::UnexpectedArgument("12".to_string())));
+    }
+}
@@ -0,0 +1,204 @@
+use crate::command::ParseError;
+
+use super::{GraphBuilder, GraphSpec, Parameters, MAX_TASKS};
+
+/// A tiled factorization of a dense matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Factorization {
+    /// Cholesky factorization of the lower triangle, with the kernels POTRF, TRSM, SYRK and GEMM
+    Cholesky,
+    /// LU factorization without pivoting, with the kernels GETRF, TRSM_L, TRSM_U and GEMM
+    Lu,
+    /// Householder QR factorization, with the kernels GEQRT, UNMQR, TSQRT and TSMQR
+    Qr,
+}
+
+/// The task graph of a factorization of a matrix split into `tiles` × `tiles` tiles,
+/// with one task per kernel call. The factorizations of the diagonal tiles are marked as critical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DenseFactorization {
+    pub factorization: Factorization,
+    pub tiles: usize,
+}
+
+/// Data accessed by the kernels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Data {
+    Tile(usize, usize),
+    /// The Householder vectors stored in the lower triangle of a diagonal tile
+    Lower(usize),
+    /// The upper triangle of a diagonal tile, holding R
+    Upper(usize),
+}
+
+impl DenseFactorization {
+    pub(crate) fn from_parameters(name: &str, params: &mut Parameters) -> Result<Self, ParseError> {
+        let factorization = match name {
+            "cholesky" => Factorization::Cholesky,
+            "lu" => Factorization::Lu,
+            _ => Factorization::Qr,
+        };
+
+        let dense = Self { factorization, tiles: params.take_if("tiles", 4, |n| *n > 0)? };
+        match dense.task_count() {
+            Some(n) if n <= MAX_TASKS => Ok(dense),
+            _ => Err(ParseError::InvalidArgument(format!("{} of more than {} tasks", name, MAX_TASKS))),
+        }
+    }
+
+    /// The number of tasks of the graph, or `None` if it does not even fit into a `usize`
+    pub fn task_count(&self) -> Option<usize> {
+        let n = self.tiles;
+        match self.factorization {
+            // a kernel call for every tile of the lower triangle left to update, in each step
+            Factorization::Cholesky => n.checked_mul(n.checked_add(1)?)?.checked_mul(n.checked_add(2)?).map(|tasks| tasks / 6),
+            // a kernel call for every tile left to update, in each step
+            Factorization::Lu | Factorization::Qr => n.checked_mul(n.checked_add(1)?)?.checked_mul(n.checked_mul(2)?.checked_add(1)?).map(|tasks| tasks / 6),
+        }
+    }
+
+    pub fn generate(&self) -> GraphSpec {
+        let mut builder = GraphBuilder::new();
+        let n = self.tiles;
+        let tile = Data::Tile;
+
+        for k in 0..n {
+            match self.factorization {
+                Factorization::Cholesky => {
+                    builder.task("POTRF", true, &[], &[tile(k, k)]);
+                    for i in k + 1..n {
+                        builder.task("TRSM", false, &[tile(k, k)], &[tile(i, k)]);
+                    }
+                    for i in k + 1..n {
+                        builder.task("SYRK", false, &[tile(i, k)], &[tile(i, i)]);
+                        for j in k + 1..i {
+                            builder.task("GEMM", false, &[tile(i, k), tile(j, k)], &[tile(i, j)]);
+                        }
+                    }
+                },
+                Factorization::Lu => {
+                    builder.task("GETRF", true, &[], &[tile(k, k)]);
+                    for j in k + 1..n {
+                        builder.task("TRSM_L", false, &[tile(k, k)], &[tile(k, j)]);
+                    }
+                    for i in k + 1..n {
+                        builder.task("TRSM_U", false, &[tile(k, k)], &[tile(i, k)]);
+                    }
+                    for i in k + 1..n {
+                        for j in k + 1..n {
+                            builder.task("GEMM", false, &[tile(i, k), tile(k, j)], &[tile(i, j)]);
+                        }
+                    }
+                },
+                Factorization::Qr => {
+                    // after GEQRT, the vectors and R of the diagonal tile are used separately, as the kernels do
+                    builder.task("GEQRT", true, &[tile(k, k)], &[Data::Lower(k), Data::Upper(k)]);
+                    for j in k + 1..n {
+                        builder.task("UNMQR", false, &[Data::Lower(k)], &[tile(k, j)]);
+                    }
+                    for i in k + 1..n {
+                        builder.task("TSQRT", false, &[], &[Data::Upper(k), tile(i, k)]);
+                        for j in k + 1..n {
+                            builder.task("TSMQR", false, &[tile(i, k)], &[tile(k, j), tile(i, j)]);
+                        }
+                    }
+                },
+            }
+        }
+
+        builder.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::ParseError, generator::MAX_TASKS, Generator, GraphSpec};
+
+    use super::{DenseFactorization, Factorization};
+
+    fn generate(factorization: Factorization, tiles: usize) -> GraphSpec {
+        DenseFactorization { factorization, tiles }.generate()
+    }
+
+    /// Indices of the tasks with `label`, in the order they were created
+    fn tasks(spec: &GraphSpec, label: &str) -> Vec<usize> {
+        let label = spec.labels.iter().position(|l| l == label).unwrap();
+        (0..spec.tasks.len()).filter(|t| spec.tasks[*t].label == label).collect()
+    }
+
+    #[test]
+    fn cholesky() {
+        let spec = generate(Factorization::Cholesky, 2);
+        assert_eq!(spec.labels, ["POTRF", "TRSM", "SYRK"]);
+        assert_eq!(spec.dependencies, [(0, 1), (1, 2), (2, 3)]);
+
+        let spec = generate(Factorization::Cholesky, 3);
+        // n (n + 1) (n + 2) / 6 tasks
+        assert_eq!(spec.tasks.len(), 10);
+        assert_eq!(spec.labels, ["POTRF", "TRSM", "SYRK", "GEMM"]);
+        assert_eq!(tasks(&spec, "POTRF"), [0, 6, 9]);
+        assert!(spec.tasks.iter().enumerate().all(|(t, task)| task.is_critical == [0, 6, 9].contains(&t)));
+
+        // step 0: POTRF 0, TRSM 1 and 2, SYRK 3, SYRK 4, GEMM 5; step 1: POTRF 6, TRSM 7, SYRK 8; step 2: POTRF 9
+        assert_eq!(spec.dependencies, [
+            (0, 1), (0, 2),
+            (1, 3), (1, 5),
+            (2, 4), (2, 5),
+            (3, 6),
+            (4, 8),
+            (5, 7),
+            (6, 7),
+            (7, 8),
+            (8, 9),
+        ]);
+    }
+
+    #[test]
+    fn lu() {
+        let spec = generate(Factorization::Lu, 3);
+        // the sum of (n - k)^2 tasks for each step k
+        assert_eq!(spec.tasks.len(), 9 + 4 + 1);
+        assert_eq!(spec.labels, ["GETRF", "TRSM_L", "TRSM_U", "GEMM"]);
+        assert_eq!(tasks(&spec, "GETRF"), [0, 9, 13]);
+
+        // GEMM of step 0 on tile (1, 1) needs the row and column panels, then the next GETRF needs it
+        assert!(spec.dependencies.contains(&(1, 5)));
+        assert!(spec.dependencies.contains(&(3, 5)));
+        assert!(spec.dependencies.contains(&(5, 9)));
+        assert!(spec.dependencies.iter().all(|(p, c)| p < c));
+    }
+
+    #[test]
+    fn qr() {
+        let spec = generate(Factorization::Qr, 2);
+        assert_eq!(spec.labels, ["GEQRT", "UNMQR", "TSQRT", "TSMQR"]);
+        // GEQRT 0, UNMQR 1, TSQRT 2, TSMQR 3, GEQRT 4
+        assert_eq!(spec.dependencies, [(0, 1), (0, 2), (1, 3), (2, 3), (3, 4)]);
+
+        let spec = generate(Factorization::Qr, 3);
+        assert_eq!(tasks(&spec, "TSQRT").len(), 3);
+        assert_eq!(tasks(&spec, "TSMQR").len(), 5);
+        // the TSQRTs of a column are applied one after another
+        let tsqrt = tasks(&spec, "TSQRT");
+        assert!(spec.dependencies.contains(&(tsqrt[0], tsqrt[1])));
+    }
+
+    #[test]
+    fn task_count_matches_graph() {
+        for factorization in [Factorization::Cholesky, Factorization::Lu, Factorization::Qr] {
+            for tiles in 1..6 {
+                let dense = DenseFactorization { factorization, tiles };
+                assert_eq!(dense.task_count(), Some(dense.generate().tasks.len()), "{:?}", dense);
+            }
+        }
+        assert_eq!(DenseFactorization { factorization: Factorization::Lu, tiles: usize::MAX }.task_count(), None);
+    }
+
+    #[test]
+    fn factorizations_are_limited_in_size() {
+        // 180 tiles give 988260 tasks
+        assert!("cholesky tiles=180".parse::<Generator>().is_ok());
+        assert_eq!("cholesky tiles=100000".parse::<Generator>(), Err(ParseError::InvalidArgument(format!("cholesky of more than {} tasks", MAX_TASKS))));
+        assert_eq!("qr tiles=200".parse::<Generator>(), Err(ParseError::InvalidArgument(format!("qr of more than {} tasks", MAX_TASKS))));
+    }
+}
@@ -0,0 +1,281 @@
+use crate::command::ParseError;
+
+use super::{GraphBuilder, GraphSpec, Parameters, MAX_TASKS};
+
+/// Task graphs with a regular structure, as common parallel programming patterns produce them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Nested fork-joins, forking into `width` branches `depth` times,
+    /// repeated `iterations` times one after another
+    ForkJoin { width: usize, depth: usize, iterations: usize },
+    /// `width` items passing through `depth` stages, where every stage handles the items in order
+    Pipeline { width: usize, depth: usize },
+    /// `iterations` sweeps over a `width` × `depth` grid updated in place,
+    /// where every cell needs the new values of its upper and left neighbours and the old values of the others
+    Wavefront { width: usize, depth: usize, iterations: usize },
+    /// `iterations` steps of a 5-point stencil on a `width` × `depth` grid,
+    /// where every cell needs itself and its four neighbours of the previous step
+    Stencil { width: usize, depth: usize, iterations: usize },
+    /// `width` map tasks, whose results are reduced pairwise, repeated `iterations` times one after another
+    MapReduce { width: usize, iterations: usize },
+}
+
+/// Cells of a grid, by the step in which they were computed
+type Cell = (usize, usize, usize);
+
+impl Pattern {
+    pub(crate) fn from_parameters(name: &str, params: &mut Parameters) -> Result<Self, ParseError> {
+        let positive = |n: &usize| *n > 0;
+        let width = |params: &mut Parameters, default| params.take_if("width", default, positive);
+
+        let pattern = match name {
+            "forkjoin" => Pattern::ForkJoin {
+                width: width(params, 2)?,
+                depth: params.take("depth", 3)?,
+                iterations: params.take_if("iterations", 1, positive)?,
+            },
+            "pipeline" => Pattern::Pipeline { width: width(params, 8)?, depth: params.take_if("depth", 4, positive)? },
+            "wavefront" => Pattern::Wavefront {
+                width: width(params, 6)?,
+                depth: params.take_if("depth", 6, positive)?,
+                iterations: params.take_if("iterations", 1, positive)?,
+            },
+            "stencil" => Pattern::Stencil {
+                width: width(params, 4)?,
+                depth: params.take_if("depth", 4, positive)?,
+                iterations: params.take_if("iterations", 3, positive)?,
+            },
+            _ => Pattern::MapReduce { width: width(params, 8)?, iterations: params.take_if("iterations", 1, positive)? },
+        };
+
+        match pattern.task_count() {
+            Some(n) if n <= MAX_TASKS => Ok(pattern),
+            _ => Err(ParseError::InvalidArgument(format!("{} of more than {} tasks", name, MAX_TASKS))),
+        }
+    }
+
+    /// The number of tasks of the graph, or `None` if it does not even fit into a `usize`
+    pub fn task_count(&self) -> Option<usize> {
+        match *self {
+            Pattern::ForkJoin { width, depth, iterations } => {
+                // a fork, its branches and a join per level
+                let per_iteration = (0..depth).try_fold(1usize, |branch, _| branch.checked_mul(width)?.checked_add(2))?;
+                per_iteration.checked_mul(iterations)
+            },
+            Pattern::Pipeline { width, depth } => width.checked_mul(depth),
+            Pattern::Wavefront { width, depth, iterations } | Pattern::Stencil { width, depth, iterations } => {
+                width.checked_mul(depth)?.checked_mul(iterations)
+            },
+            // every reduce task combines two results into one
+            Pattern::MapReduce { width, iterations } => (width.checked_mul(2)? - 1).checked_mul(iterations),
+        }
+    }
+
+    pub fn generate(&self) -> GraphSpec {
+        match *self {
+            Pattern::ForkJoin { width, depth, iterations } => {
+                let mut builder = GraphBuilder::<()>::new();
+                let mut last = None;
+                for _ in 0..iterations {
+                    last = Some(fork_join(&mut builder, width, depth, last));
+                }
+                builder.finish()
+            },
+            Pattern::Pipeline { width, depth } => {
+                let mut builder = GraphBuilder::<()>::new();
+                // the task of each stage for the previous item
+                let mut previous_item: Vec<Option<usize>> = vec![None; depth];
+                for _ in 0..width {
+                    let mut previous_stage = None;
+                    for (stage, previous) in previous_item.iter_mut().enumerate() {
+                        let parents: Vec<_> = previous_stage.into_iter().chain(*previous).collect();
+                        let task = builder.task_after(&format!("stage_{}", stage), &parents);
+                        *previous = Some(task);
+                        previous_stage = Some(task);
+                    }
+                }
+                builder.finish()
+            },
+            Pattern::Wavefront { width, depth, iterations } => {
+                let mut builder = GraphBuilder::new();
+                for sweep in 0..iterations {
+                    let label = format!("sweep_{}", sweep);
+                    for i in 0..width {
+                        for j in 0..depth {
+                            let reads = neighbours(i, j, width, depth);
+                            builder.task(&label, false, &reads, &[(i, j)]);
+                        }
+                    }
+                }
+                builder.finish()
+            },
+            Pattern::Stencil { width, depth, iterations } => {
+                let mut builder = GraphBuilder::<Cell>::new();
+                for step in 0..iterations {
+                    let label = format!("step_{}", step);
+                    for i in 0..width {
+                        for j in 0..depth {
+                            let reads: Vec<_> = match step {
+                                0 => Vec::new(),
+                                _ => neighbours(i, j, width, depth).into_iter().chain([(i, j)]).map(|(i, j)| (step - 1, i, j)).collect(),
+                            };
+                            builder.task(&label, false, &reads, &[(step, i, j)]);
+                        }
+                    }
+                }
+                builder.finish()
+            },
+            Pattern::MapReduce { width, iterations } => {
+                let mut builder = GraphBuilder::<()>::new();
+                let mut result = None;
+                for _ in 0..iterations {
+                    let parents: Vec<_> = result.into_iter().collect();
+                    let mut level: Vec<_> = (0..width).map(|_| builder.task_after("map", &parents)).collect();
+                    while level.len() > 1 {
+                        level = level.chunks(2)
+                            .map(|pair| match pair {
+                                [single] => *single,
+                                pair => builder.task_after("reduce", pair),
+                            })
+                            .collect();
+                    }
+                    result = level.first().copied();
+                }
+                builder.finish()
+            },
+        }
+    }
+}
+
+/// Adds a fork-join of `depth` levels after `after`, and returns the task that joins it.
+/// The branches are added depth first, each of them completely before the next one.
+fn fork_join(builder: &mut GraphBuilder<()>, width: usize, depth: usize, after: Option<usize>) -> usize {
+    // the forks whose branches are being added, with the last tasks of the branches added so far
+    let mut open: Vec<(usize, Vec<usize>)> = Vec::with_capacity(depth);
+    let mut parent = after;
+
+    loop {
+        while open.len() < depth {
+            let fork = builder.task_after("fork", &Vec::from_iter(parent));
+            open.push((fork, Vec::with_capacity(width)));
+            parent = Some(fork);
+        }
+        let mut last = builder.task_after("work", &Vec::from_iter(parent));
+
+        // join all forks whose branches are complete now
+        loop {
+            let Some((fork, branches)) = open.last_mut() else {
+                return last;
+            };
+            branches.push(last);
+            if branches.len() < width {
+                parent = Some(*fork);
+                break;
+            }
+
+            let (_, branches) = open.pop().expect("there is an open fork");
+            last = builder.task_after("join", &branches);
+        }
+    }
+}
+
+/// The cells above, left of, below and right of `(i, j)` on a `width` × `depth` grid
+fn neighbours(i: usize, j: usize, width: usize, depth: usize) -> Vec<(usize, usize)> {
+    let mut cells = Vec::with_capacity(4);
+    if i > 0 {
+        cells.push((i - 1, j));
+    }
+    if j > 0 {
+        cells.push((i, j - 1));
+    }
+    if i + 1 < width {
+        cells.push((i + 1, j));
+    }
+    if j + 1 < depth {
+        cells.push((i, j + 1));
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::ParseError, Generator};
+
+    use super::{Pattern, MAX_TASKS};
+
+    #[test]
+    fn fork_join() {
+        let spec = Pattern::ForkJoin { width: 2, depth: 1, iterations: 2 }.generate();
+        assert_eq!(spec.labels, ["fork", "work", "join"]);
+        // fork 0, work 1 and 2, join 3, then again after join 3
+        assert_eq!(spec.dependencies, [(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (4, 5), (4, 6), (5, 7), (6, 7)]);
+
+        let spec = Pattern::ForkJoin { width: 3, depth: 2, iterations: 1 }.generate();
+        // 9 work tasks, 4 forks and 4 joins
+        assert_eq!(spec.tasks.len(), 17);
+        assert_eq!(Pattern::ForkJoin { width: 3, depth: 2, iterations: 1 }.task_count(), Some(17));
+
+        // deep nesting does not need a deep stack
+        let spec = Pattern::ForkJoin { width: 1, depth: 100_000, iterations: 1 }.generate();
+        assert_eq!(spec.tasks.len(), 200_001);
+    }
+
+    #[test]
+    fn patterns_are_limited_in_size() {
+        assert!("forkjoin width=1 depth=100000".parse::<Generator>().is_ok());
+        assert_eq!(
+            "forkjoin width=2 depth=100".parse::<Generator>(),
+            Err(ParseError::InvalidArgument(format!("forkjoin of more than {} tasks", MAX_TASKS))),
+        );
+        assert!("stencil width=1000 depth=1000 iterations=2".parse::<Generator>().is_err());
+        assert!("mapreduce width=500000".parse::<Generator>().is_ok());
+
+        for pattern in ["forkjoin", "pipeline", "wavefront", "stencil", "mapreduce"] {
+            let Ok(Generator::Pattern(pattern)) = pattern.parse() else {
+                panic!("{} is a pattern", pattern);
+            };
+            assert_eq!(pattern.task_count(), Some(pattern.generate().tasks.len()));
+        }
+    }
+
+    #[test]
+    fn pipeline() {
+        let spec = Pattern::Pipeline { width: 3, depth: 2 }.generate();
+        assert_eq!(spec.labels, ["stage_0", "stage_1"]);
+        // item i in stage s is task 2 i + s
+        assert_eq!(spec.dependencies, [(0, 1), (0, 2), (1, 3), (2, 3), (2, 4), (3, 5), (4, 5)]);
+    }
+
+    #[test]
+    fn wavefront() {
+        let spec = Pattern::Wavefront { width: 2, depth: 2, iterations: 1 }.generate();
+        // cell (i, j) is task 2 i + j, the anti-diagonal in the middle runs in parallel
+        assert_eq!(spec.dependencies, [(0, 1), (0, 2), (1, 3), (2, 3)]);
+
+        let spec = Pattern::Wavefront { width: 3, depth: 3, iterations: 2 }.generate();
+        assert_eq!(spec.labels, ["sweep_0", "sweep_1"]);
+        // the second sweep needs the first one to have passed the neighbours of its first cell
+        assert!(spec.dependencies.contains(&(1, 9)) && spec.dependencies.contains(&(3, 9)));
+    }
+
+    #[test]
+    fn stencil() {
+        let spec = Pattern::Stencil { width: 3, depth: 3, iterations: 2 }.generate();
+        assert_eq!(spec.tasks.len(), 18);
+
+        let parents = |task| spec.dependencies.iter().filter(|(_, c)| *c == task).map(|(p, _)| *p).collect::<Vec<_>>();
+        // the center of the second step needs the center of the first one and its four neighbours
+        assert_eq!(parents(13), [1, 3, 4, 5, 7]);
+        // a corner only has two neighbours
+        assert_eq!(parents(9), [0, 1, 3]);
+        assert!(parents(4).is_empty());
+    }
+
+    #[test]
+    fn map_reduce() {
+        let spec = Pattern::MapReduce { width: 3, iterations: 2 }.generate();
+        assert_eq!(spec.labels, ["map", "reduce"]);
+        // maps 0 to 2, then 0 and 1 are reduced by 3, which is reduced with 2 by 4, the next maps follow 4
+        assert_eq!(spec.dependencies, [(0, 3), (1, 3), (2, 4), (3, 4), (4, 5), (4, 6), (4, 7), (5, 8), (6, 8), (7, 9), (8, 9)]);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{command::ParseError, rng::Rng};
+
+use super::{GraphSpec, Parameters, TaskSpec, MAX_TASKS};
+
+/// The most pairs of tasks a random graph may consider as dependencies, generating it takes time proportional to them
+pub const MAX_PAIRS: usize = 20_000_000;
+
+/// How the dependencies of a random graph are chosen.
+/// In all shapes, tasks only depend on tasks created before them, so the graph never has cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RandomShape {
+    /// The tasks are split into layers of equal size, and every task depends on
+    /// at least one task of the previous layer
+    Layered { layers: usize },
+    /// Every pair of tasks is connected with the same probability (Erdős–Rényi)
+    Uniform,
+    /// Every task depends on at most `max_in` tasks, and at most `max_out` tasks depend on every task
+    Bounded { max_in: usize, max_out: usize },
+}
+
+/// A random task graph, which only depends on its parameters and the seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct RandomGraph {
+    pub shape: RandomShape,
+    pub tasks: usize,
+    /// Probability of each possible dependency
+    pub density: f64,
+    /// Number of labels the tasks are randomly assigned to
+    pub labels: usize,
+    pub seed: u64,
+}
+
+impl Default for RandomGraph {
+    fn default() -> Self {
+        Self { shape: RandomShape::Uniform, tasks: 20, density: 0.2, labels: 3, seed: 0 }
+    }
+}
+
+impl RandomGraph {
+    pub(crate) fn from_parameters(name: &str, params: &mut Parameters) -> Result<Self, ParseError> {
+        let default = RandomGraph::default();
+
+        let shape = match name {
+            "layered" => RandomShape::Layered { layers: params.take_if("layers", 4, |n| *n > 0)? },
+            "bounded" => RandomShape::Bounded {
+                max_in: params.take("max-in", 2)?,
+                max_out: params.take("max-out", 2)?,
+            },
+            _ => RandomShape::Uniform,
+        };
+
+        let graph = Self {
+            shape,
+            tasks: params.take("tasks", default.tasks)?,
+            density: params.take_if("density", default.density, |p| (0.0..=1.0).contains(p))?,
+            labels: params.take_if("labels", default.labels, |n| *n > 0)?,
+            seed: params.take("seed", default.seed)?,
+        };
+
+        if graph.tasks > MAX_TASKS {
+            return Err(ParseError::InvalidArgument(format!("{} graph of more than {} tasks", name, MAX_TASKS)));
+        }
+        match graph.candidate_pairs() {
+            Some(n) if n <= MAX_PAIRS => Ok(graph),
+            _ => Err(ParseError::InvalidArgument(format!("{} graph considering more than {} dependencies", name, MAX_PAIRS))),
+        }
+    }
+
+    /// The number of pairs of tasks that are considered as dependencies, or `None` if it does not even fit into a `usize`
+    pub fn candidate_pairs(&self) -> Option<usize> {
+        let all_pairs = self.tasks.checked_mul(self.tasks.saturating_sub(1))? / 2;
+
+        match self.shape {
+            RandomShape::Layered { layers } => {
+                // every task is paired with the tasks of the previous layer
+                let layers = layers.clamp(1, self.tasks.max(1));
+                let start = |layer: usize| Some(layer.checked_mul(self.tasks)? / layers);
+                (1..layers).try_fold(0usize, |pairs, layer| {
+                    let previous = start(layer)? - start(layer - 1)?;
+                    pairs.checked_add(previous.checked_mul(start(layer + 1)? - start(layer)?)?)
+                })
+            },
+            RandomShape::Uniform => Some(all_pairs),
+            // up to `max_in` parents are picked among all tasks before each task
+            RandomShape::Bounded { max_in, .. } => all_pairs.checked_mul(max_in),
+        }
+    }
+
+    pub fn generate(&self) -> GraphSpec {
+        let mut rng = Rng::new(self.seed);
+
+        let labels = (0..self.labels).map(|i| format!("random_{}", i)).collect();
+        let tasks = (0..self.tasks).map(|_| TaskSpec { label: rng.index(self.labels), is_critical: false }).collect();
+
+        let dependencies = match self.shape {
+            RandomShape::Layered { layers } => self.layered(layers, &mut rng),
+            RandomShape::Uniform => self.uniform(&mut rng),
+            RandomShape::Bounded { max_in, max_out } => self.bounded(max_in, max_out, &mut rng),
+        };
+
+        GraphSpec { labels, tasks, dependencies }
+    }
+
+    fn layered(&self, layers: usize, rng: &mut Rng) -> Vec<(usize, usize)> {
+        let layers = layers.clamp(1, self.tasks.max(1));
+        let start = |layer: usize| layer * self.tasks / layers;
+        let mut dependencies = Vec::new();
+
+        for layer in 1..layers {
+            let previous = start(layer - 1)..start(layer);
+            for child in start(layer)..start(layer + 1) {
+                let parents: Vec<_> = previous.clone().filter(|_| rng.chance(self.density)).collect();
+                match parents.is_empty() {
+                    true => dependencies.push((previous.start + rng.index(previous.len()), child)),
+                    false => dependencies.extend(parents.into_iter().map(|parent| (parent, child))),
+                }
+            }
+        }
+
+        dependencies
+    }
+
+    fn uniform(&self, rng: &mut Rng) -> Vec<(usize, usize)> {
+        let mut dependencies = Vec::new();
+
+        for child in 0..self.tasks {
+            for parent in 0..child {
+                if rng.chance(self.density) {
+                    dependencies.push((parent, child));
+                }
+            }
+        }
+
+        dependencies
+    }
+
+    fn bounded(&self, max_in: usize, max_out: usize, rng: &mut Rng) -> Vec<(usize, usize)> {
+        let mut dependencies = Vec::new();
+        let mut out_degree = vec![0; self.tasks];
+
+        for child in 1..self.tasks {
+            let mut parents = Vec::new();
+            for _ in 0..max_in {
+                if !rng.chance(self.density) {
+                    continue;
+                }
+
+                let candidates: Vec<_> = (0..child).filter(|p| out_degree[*p] < max_out && !parents.contains(p)).collect();
+                if candidates.is_empty() {
+                    break;
+                }
+                let parent = candidates[rng.index(candidates.len())];
+                out_degree[parent] += 1;
+                parents.push(parent);
+            }
+
+            parents.sort();
+            dependencies.extend(parents.into_iter().map(|parent| (parent, child)));
+        }
+
+        dependencies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::ParseError, generator::MAX_TASKS, Generator};
+
+    use super::{RandomGraph, RandomShape, MAX_PAIRS};
+
+    fn graph(shape: RandomShape, tasks: usize, density: f64, seed: u64) -> RandomGraph {
+        RandomGraph { shape, tasks, density, seed, ..RandomGraph::default() }
+    }
+
+    #[test]
+    fn seed_reproduces_graph() {
+        for shape in [RandomShape::Uniform, RandomShape::Layered { layers: 5 }, RandomShape::Bounded { max_in: 3, max_out: 2 }] {
+            let spec = graph(shape, 50, 0.3, 42).generate();
+            assert_eq!(spec, graph(shape, 50, 0.3, 42).generate());
+            assert_ne!(spec, graph(shape, 50, 0.3, 43).generate());
+
+            assert_eq!(spec.tasks.len(), 50);
+            assert!(spec.tasks.iter().all(|t| t.label < 3));
+            assert!(spec.dependencies.iter().all(|(p, c)| p < c), "{:?} has a backward dependency", shape);
+        }
+    }
+
+    #[test]
+    fn uniform_density() {
+        assert!(graph(RandomShape::Uniform, 10, 0.0, 0).generate().dependencies.is_empty());
+        assert_eq!(graph(RandomShape::Uniform, 10, 1.0, 0).generate().dependencies.len(), 45);
+    }
+
+    #[test]
+    fn layered_connects_neighbouring_layers() {
+        let spec = graph(RandomShape::Layered { layers: 4 }, 12, 0.5, 1).generate();
+        let layer = |task: usize| task / 3;
+
+        assert!(spec.dependencies.iter().all(|&(p, c)| layer(p) + 1 == layer(c)));
+        for child in 3..12 {
+            assert!(spec.dependencies.iter().any(|&(_, c)| c == child), "task {} has no parent", child);
+        }
+    }
+
+    #[test]
+    fn bounded_degrees() {
+        let spec = graph(RandomShape::Bounded { max_in: 2, max_out: 3 }, 100, 0.8, 7).generate();
+
+        for task in 0..100 {
+            assert!(spec.dependencies.iter().filter(|(_, c)| *c == task).count() <= 2);
+            assert!(spec.dependencies.iter().filter(|(p, _)| *p == task).count() <= 3);
+        }
+        assert!(!spec.dependencies.is_empty());
+    }
+
+    #[test]
+    fn candidate_pairs_are_all_dependencies_at_full_density() {
+        for shape in [RandomShape::Uniform, RandomShape::Layered { layers: 4 }, RandomShape::Layered { layers: 7 }] {
+            let graph = graph(shape, 30, 1.0, 0);
+            assert_eq!(graph.candidate_pairs(), Some(graph.generate().dependencies.len()), "{:?}", shape);
+        }
+        assert_eq!(graph(RandomShape::Bounded { max_in: 3, max_out: 2 }, 30, 1.0, 0).candidate_pairs(), Some(3 * 435));
+        assert_eq!(graph(RandomShape::Bounded { max_in: usize::MAX, max_out: 2 }, 30, 1.0, 0).candidate_pairs(), None);
+    }
+
+    #[test]
+    fn random_graphs_are_limited_in_size() {
+        assert!("layered tasks=1000000 layers=1000000".parse::<Generator>().is_ok());
+        assert_eq!(
+            "layered tasks=1000001 layers=1000001".parse::<Generator>(),
+            Err(ParseError::InvalidArgument(format!("layered graph of more than {} tasks", MAX_TASKS))),
+        );
+        assert_eq!(
+            "random tasks=100000".parse::<Generator>(),
+            Err(ParseError::InvalidArgument(format!("random graph considering more than {} dependencies", MAX_PAIRS))),
+        );
+        assert_eq!(
+            "bounded tasks=100 max-in=18446744073709551615".parse::<Generator>(),
+            Err(ParseError::InvalidArgument(format!("bounded graph considering more than {} dependencies", MAX_PAIRS))),
+        );
+    }
+}
@@ -0,0 +1,352 @@
+use std::{collections::BTreeSet, fmt::Display, io::BufRead, ops::Range, str::FromStr};
+
+use crate::command::ParseError;
+
+use super::{GraphBuilder, GraphSpec, MAX_TASKS};
+
+/// The nonzero pattern of a square sparse matrix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseMatrix {
+    pub size: usize,
+    /// Row and column of every nonzero, counted from 0
+    pub entries: Vec<(usize, usize)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MatrixError {
+    Read(String),
+    /// The first line is not the header of a MatrixMarket file in coordinate format
+    Header(String),
+    Parse { line: usize, message: String },
+    /// There is no line with the size of the matrix after the header
+    MissingSize,
+    MissingEntries { expected: usize, found: usize },
+    NotSquare { rows: usize, cols: usize },
+    /// The matrix has more columns than tasks a generated graph may have
+    TooLarge(usize),
+}
+
+impl Display for MatrixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatrixError::Read(e) => write!(f, "Unable to read matrix: {}", e),
+            MatrixError::Header(header) => write!(f, "Not a sparse MatrixMarket matrix: {}", header),
+            MatrixError::Parse { line, message } => write!(f, "Invalid line {}: {}", line, message),
+            MatrixError::MissingSize => write!(f, "The line with the size of the matrix is missing."),
+            MatrixError::MissingEntries { expected, found } => write!(f, "Expected {} entries, found {}.", expected, found),
+            MatrixError::NotSquare { rows, cols } => write!(f, "Only square matrices can be factorized, this one is {} × {}.", rows, cols),
+            MatrixError::TooLarge(size) => write!(f, "Matrices of more than {} columns are not supported, this one has {}.", MAX_TASKS, size),
+        }
+    }
+}
+
+impl std::error::Error for MatrixError { }
+
+/// Reads the nonzero pattern of a matrix in the MatrixMarket coordinate format.
+/// Values are ignored, so all fields (real, complex, integer and pattern) and symmetries are accepted.
+pub fn read_matrix_market(reader: impl BufRead) -> Result<SparseMatrix, MatrixError> {
+    let mut lines = reader.lines().enumerate().map(|(idx, line)| line.map(|line| (idx + 1, line)).map_err(|e| MatrixError::Read(e.to_string())));
+
+    let (_, header) = lines.next().transpose()?.ok_or(MatrixError::Header(String::new()))?;
+    let words: Vec<_> = header.split_whitespace().map(str::to_lowercase).collect();
+    if words.len() < 3 || words[0] != "%%matrixmarket" || words[1] != "matrix" || words[2] != "coordinate" {
+        return Err(MatrixError::Header(header));
+    }
+
+    let mut size = None;
+    let mut entries = Vec::new();
+
+    for line in lines {
+        let (number, line) = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('%') {
+            continue;
+        }
+
+        let numbers = line.split_whitespace()
+            .take(if size.is_none() { 3 } else { 2 })
+            .map(|n| n.parse::<usize>().map_err(|_| MatrixError::Parse { line: number, message: format!("{} is not an index", n) }))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match (size, numbers.as_slice()) {
+            (None, &[rows, cols, nonzeros]) => {
+                if rows != cols {
+                    return Err(MatrixError::NotSquare { rows, cols });
+                }
+                // the graph has tasks for every supernode, of which there can be one per column
+                if rows > MAX_TASKS {
+                    return Err(MatrixError::TooLarge(rows));
+                }
+                size = Some((rows, nonzeros));
+            },
+            (Some((n, _)), &[row, col]) if (1..=n).contains(&row) && (1..=n).contains(&col) => entries.push((row - 1, col - 1)),
+            (Some((n, _)), &[_, _]) => return Err(MatrixError::Parse { line: number, message: format!("index out of range 1 to {}", n) }),
+            _ => return Err(MatrixError::Parse { line: number, message: "missing indices".to_string() }),
+        }
+    }
+
+    let (size, nonzeros) = size.ok_or(MatrixError::MissingSize)?;
+    if entries.len() != nonzeros {
+        return Err(MatrixError::MissingEntries { expected: nonzeros, found: entries.len() });
+    }
+
+    Ok(SparseMatrix { size, entries })
+}
+
+/// The order in which the columns are eliminated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EliminationOrder {
+    /// The order of the matrix
+    #[default]
+    Natural,
+    /// Always eliminate a column of minimum degree next, which reduces fill-in
+    MinimumDegree,
+}
+
+impl FromStr for EliminationOrder {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "natural" => Ok(EliminationOrder::Natural),
+            "mindegree" => Ok(EliminationOrder::MinimumDegree),
+            _ => Err(ParseError::InvalidArgument(s.to_string())),
+        }
+    }
+}
+
+/// The structure of the Cholesky factor L of a symmetric matrix, with the pattern of A + Aᵀ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolicFactorization {
+    /// The column of the matrix that is eliminated in each step
+    pub permutation: Vec<usize>,
+    /// Parent of every column in the elimination tree, in elimination order
+    pub parent: Vec<Option<usize>>,
+    /// Ranges of columns with the same structure below the diagonal, which are factorized together
+    pub supernodes: Vec<Range<usize>>,
+    /// Rows of the nonzeros below the diagonal of every column of L
+    structure: Vec<BTreeSet<usize>>,
+}
+
+impl SymbolicFactorization {
+    pub fn analyze(matrix: &SparseMatrix, order: EliminationOrder) -> Self {
+        let n = matrix.size;
+        let mut adjacency = vec![BTreeSet::new(); n];
+        for &(row, col) in &matrix.entries {
+            if row != col {
+                adjacency[row].insert(col);
+                adjacency[col].insert(row);
+            }
+        }
+
+        let permutation = match order {
+            EliminationOrder::Natural => (0..n).collect(),
+            EliminationOrder::MinimumDegree => minimum_degree(&adjacency),
+        };
+        let mut position = vec![0; n];
+        for (k, &column) in permutation.iter().enumerate() {
+            position[column] = k;
+        }
+        let adjacency: Vec<BTreeSet<usize>> = permutation.iter()
+            .map(|&column| adjacency[column].iter().map(|&c| position[c]).collect())
+            .collect();
+
+        let parent = elimination_tree(&adjacency);
+
+        // the structure of a column is its own pattern below the diagonal, together with that of its children
+        let mut structure: Vec<BTreeSet<usize>> = Vec::with_capacity(n);
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for j in 0..n {
+            let mut rows: BTreeSet<_> = adjacency[j].range(j + 1..).copied().collect();
+            for &child in &children[j] {
+                rows.extend(structure[child].range(j + 1..).copied());
+            }
+            if let Some(p) = parent[j] {
+                children[p].push(j);
+            }
+            structure.push(rows);
+        }
+
+        // fundamental supernodes: a column joins the previous one if it is its only child and has one row less
+        let mut supernodes: Vec<Range<usize>> = Vec::new();
+        for j in 0..n {
+            match supernodes.last_mut() {
+                Some(last) if j > 0 && parent[j - 1] == Some(j) && children[j].len() == 1 && structure[j - 1].len() == structure[j].len() + 1 => last.end = j + 1,
+                _ => supernodes.push(j..j + 1),
+            }
+        }
+
+        Self { permutation, parent, supernodes, structure }
+    }
+
+    /// Number of nonzeros in L, including the diagonal
+    pub fn nonzeros(&self) -> usize {
+        self.structure.iter().map(|rows| rows.len() + 1).sum()
+    }
+
+    /// The task graph of a right-looking supernodal factorization: for every supernode, POTRF factorizes
+    /// its diagonal block, TRSM the rows below it, and a GEMM updates each supernode these rows belong to.
+    /// The POTRF tasks are marked as critical.
+    pub fn graph(&self) -> GraphSpec {
+        let mut supernode_of = vec![0; self.parent.len()];
+        for (s, columns) in self.supernodes.iter().enumerate() {
+            supernode_of[columns.clone()].fill(s);
+        }
+
+        let mut builder = GraphBuilder::new();
+        for (s, columns) in self.supernodes.iter().enumerate() {
+            builder.task("POTRF", true, &[], &[s]);
+
+            let rows = &self.structure[columns.end - 1];
+            if rows.is_empty() {
+                continue;
+            }
+            builder.task("TRSM", false, &[], &[s]);
+
+            let targets: BTreeSet<_> = rows.iter().map(|&row| supernode_of[row]).collect();
+            for target in targets {
+                builder.task("GEMM", false, &[s], &[target]);
+            }
+        }
+
+        builder.finish()
+    }
+}
+
+/// Computes the elimination tree with Liu's algorithm, compressing the paths to the roots found so far
+fn elimination_tree(adjacency: &[BTreeSet<usize>]) -> Vec<Option<usize>> {
+    let n = adjacency.len();
+    let mut parent = vec![None; n];
+    let mut ancestor: Vec<Option<usize>> = vec![None; n];
+
+    for (k, neighbours) in adjacency.iter().enumerate() {
+        for &i in neighbours.range(..k) {
+            let mut i = i;
+            loop {
+                match ancestor[i] {
+                    Some(a) if a == k => break,
+                    Some(a) => {
+                        ancestor[i] = Some(k);
+                        i = a;
+                    },
+                    None => {
+                        ancestor[i] = Some(k);
+                        parent[i] = Some(k);
+                        break;
+                    },
+                }
+            }
+        }
+    }
+
+    parent
+}
+
+/// Eliminates the nodes of the graph one after another, always choosing one with the fewest neighbours left.
+/// Its neighbours become a clique, as they do when eliminating its column.
+fn minimum_degree(adjacency: &[BTreeSet<usize>]) -> Vec<usize> {
+    let mut graph = adjacency.to_vec();
+    let mut remaining: BTreeSet<usize> = (0..graph.len()).collect();
+    let mut order = Vec::with_capacity(graph.len());
+
+    while let Some(node) = remaining.iter().copied().min_by_key(|&v| graph[v].len()) {
+        remaining.remove(&node);
+        order.push(node);
+
+        let neighbours = std::mem::take(&mut graph[node]);
+        for &a in &neighbours {
+            graph[a].remove(&node);
+            graph[a].extend(neighbours.iter().copied().filter(|&b| b != a));
+        }
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_matrix_market, EliminationOrder, MatrixError, SparseMatrix, SymbolicFactorization};
+
+    /// A star: column 0 is connected to all others
+    fn arrow(n: usize) -> SparseMatrix {
+        let entries = (0..n).map(|i| (i, i)).chain((1..n).map(|i| (i, 0))).collect();
+        SparseMatrix { size: n, entries }
+    }
+
+    #[test]
+    fn read_coordinate_matrix() {
+        let mtx = "\
+%%MatrixMarket matrix coordinate real symmetric
+% a comment
+3 3 4
+1 1 4.0
+2 1 -1.0
+
+3 2 -1.0
+3 3 4.0
+";
+        let matrix = read_matrix_market(mtx.as_bytes()).unwrap();
+        assert_eq!(matrix, SparseMatrix { size: 3, entries: vec![(0, 0), (1, 0), (2, 1), (2, 2)] });
+    }
+
+    #[test]
+    fn read_errors() {
+        let read = |mtx: &str| read_matrix_market(mtx.as_bytes());
+
+        assert!(matches!(read("%%MatrixMarket matrix array real general\n2 2\n"), Err(MatrixError::Header(_))));
+        assert_eq!(read("%%MatrixMarket matrix coordinate pattern general\n2 3 1\n1 1\n"), Err(MatrixError::NotSquare { rows: 2, cols: 3 }));
+        assert_eq!(read("%%MatrixMarket matrix coordinate pattern general\n2 2 2\n1 1\n"), Err(MatrixError::MissingEntries { expected: 2, found: 1 }));
+        assert_eq!(read("%%MatrixMarket matrix coordinate pattern general\n% no size\n"), Err(MatrixError::MissingSize));
+        assert_eq!(read("%%MatrixMarket matrix coordinate pattern general\n1000000000000 1000000000000 1\n"), Err(MatrixError::TooLarge(1_000_000_000_000)));
+        assert_eq!(
+            read("%%MatrixMarket matrix coordinate pattern general\n2 2 1\n3 1\n"),
+            Err(MatrixError::Parse { line: 3, message: "index out of range 1 to 2".to_string() }),
+        );
+        assert_eq!(
+            read("%%MatrixMarket matrix coordinate pattern general\n2 2 1\n1 x\n"),
+            Err(MatrixError::Parse { line: 3, message: "x is not an index".to_string() }),
+        );
+    }
+
+    #[test]
+    fn tridiagonal() {
+        let entries = (0..4).flat_map(|i| [(i, i), (i + 1, i)]).filter(|&(r, _)| r < 4).collect();
+        let symbolic = SymbolicFactorization::analyze(&SparseMatrix { size: 4, entries }, EliminationOrder::Natural);
+
+        assert_eq!(symbolic.parent, [Some(1), Some(2), Some(3), None]);
+        assert_eq!(symbolic.supernodes, [0..1, 1..2, 2..4]);
+        assert_eq!(symbolic.nonzeros(), 7);
+
+        let spec = symbolic.graph();
+        assert_eq!(spec.labels, ["POTRF", "TRSM", "GEMM"]);
+        // POTRF, TRSM and GEMM of the first two supernodes, then POTRF of the last
+        assert_eq!(spec.dependencies, [(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (5, 6)]);
+    }
+
+    #[test]
+    fn minimum_degree_avoids_fill() {
+        let natural = SymbolicFactorization::analyze(&arrow(4), EliminationOrder::Natural);
+        // eliminating the center first fills the whole matrix, which becomes a single supernode
+        assert_eq!(natural.nonzeros(), 10);
+        assert_eq!(natural.supernodes, vec![0..4]);
+        assert_eq!(natural.graph().tasks.len(), 1);
+
+        let ordered = SymbolicFactorization::analyze(&arrow(4), EliminationOrder::MinimumDegree);
+        // once two leaves are gone, the center is a leaf itself
+        assert_eq!(ordered.permutation, [1, 2, 0, 3]);
+        assert_eq!(ordered.parent, [Some(2), Some(2), Some(3), None]);
+        assert_eq!(ordered.nonzeros(), 7);
+        assert_eq!(ordered.supernodes, [0..1, 1..2, 2..4]);
+
+        // the two leaves are independent, their updates of the last supernode are applied one after another
+        let spec = ordered.graph();
+        assert_eq!(spec.tasks.len(), 7);
+        assert_eq!(spec.dependencies, [(0, 1), (1, 2), (2, 5), (3, 4), (4, 5), (5, 6)]);
+    }
+
+    #[test]
+    fn elimination_order_parse() {
+        assert_eq!("mindegree".parse(), Ok(EliminationOrder::MinimumDegree));
+        assert!("amd".parse::<EliminationOrder>().is_err());
+    }
+}
@@ -55,4 +55,62 @@ macro_rules! match_or_continue {
     };
 }
 
-pub use match_or_continue;
\ No newline at end of file
+pub use match_or_continue;
+
+/// Implements all methods of `EventSink` by converting the arguments to an `Event`
+/// and passing it to `self.$handle`. Used inside an `impl EventSink for ...` block.
+macro_rules! impl_event_sink_via {
+    ($handle:ident) => {
+        fn pre_init(&mut self, rt: u64) {
+            self.$handle($crate::Event::PreInit { rt })
+        }
+
+        fn init(&mut self, n_threads: u64) {
+            self.$handle($crate::Event::Init { n_threads })
+        }
+
+        fn add_task(&mut self, task_id: u64, func_id: u64, priority: u64, scope_id: u64) {
+            self.$handle($crate::Event::AddTask { task_id, func_id, priority, scope_id })
+        }
+
+        fn register_function(&mut self, func_id: u64, name: &std::ffi::CStr) {
+            self.$handle($crate::Event::RegisterFunction { func_id, name: name.to_owned() })
+        }
+
+        fn add_dependency(&mut self, to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64) {
+            self.$handle($crate::Event::AddDependency { to_id, from_id, memaddr, orig_memaddr })
+        }
+
+        fn add_task_to_queue(&mut self, task_id: u64, thread_id: u64) {
+            self.$handle($crate::Event::AddTaskToQueue { task_id, thread_id })
+        }
+
+        fn pre_run_task(&mut self, task_id: u64, thread_id: u64) {
+            self.$handle($crate::Event::PreRunTask { task_id, thread_id })
+        }
+
+        fn run_task(&mut self, task_id: u64) {
+            self.$handle($crate::Event::RunTask { task_id })
+        }
+
+        fn post_run_task(&mut self, task_id: u64) {
+            self.$handle($crate::Event::PostRunTask { task_id })
+        }
+
+        fn remove_task(&mut self, task_id: u64) {
+            self.$handle($crate::Event::RemoveTask { task_id })
+        }
+
+        fn barrier(&mut self) {
+            self.$handle($crate::Event::Barrier)
+        }
+
+        fn wait_on(&mut self, task_id: u64) {
+            self.$handle($crate::Event::WaitOn { task_id })
+        }
+
+        fn finish(&mut self) {
+            self.$handle($crate::Event::Finish)
+        }
+    };
+}
@@ -1,10 +1,40 @@
 #[macro_use]
 pub mod helper_macros;
+pub mod command;
+pub mod dependency;
+pub mod event;
+pub mod event_log;
+pub mod event_sink;
+pub mod executor;
+pub mod generator;
+#[cfg(feature = "libayudame")]
+pub mod libayudame;
+pub mod lifecycle;
+pub mod options;
+pub mod rng;
+pub mod scheduler;
+pub mod simulator;
        
+pub use command::Command;
+pub use dependency::{DependencyError, DuplicatePolicy, RemovalError, RemovalPolicy};
+pub use event::Event;
+pub use event_log::RecordingSink;
+pub use event_sink::{EventSink, PrintSink, SyncEventSink};
+pub use executor::{Execution, ExecutionReport};
+pub use generator::{Generator, GraphSpec};
+#[cfg(feature = "libayudame")]
+pub use libayudame::LibAyudameSink;
+pub use lifecycle::{BarrierPolicy, TaskState, TransitionError};
+pub use options::Options;
+pub use scheduler::{ReadyTask, Scheduler, SchedulingPolicy};
+pub use simulator::{Durations, SimulationReport};
+
 use std::{
+    ffi::CStr,
     fmt::Write,
     os::raw::c_char,
-    rc::{Rc, Weak}, cell::{RefCell, RefMut}, fmt::Display,
+    rc::{Rc, Weak}, cell::{Cell, RefCell, RefMut}, fmt::Display,
+    collections::{hash_map::Entry, BTreeMap, HashMap, VecDeque},
 };
 
 #[derive(Debug)]
@@ -48,11 +78,37 @@ impl TryFrom<u64> for InputTypes {
     }
 }
 
+/// The most threads the runtime may have, larger counts are refused when parsing them
+pub const MAX_THREADS: u64 = 4096;
+
 #[derive(Debug)]
 pub struct AppState {
+    /// Runtime id sent with pre_init
+    pub runtime_id: u64,
+    /// Number of threads sent with init, thread ids have to be below it
+    pub n_threads: u64,
     pub is_pre_init: bool,
     pub is_init: bool,
+    pub is_finished: bool,
+    /// Send events even if they break the protocol
+    pub chaos: bool,
+    /// Accept dependencies that close a cycle in the task graph
+    pub allow_cycles: bool,
+    /// What happens to the dependents of removed tasks, unless given otherwise
+    pub removal_policy: RemovalPolicy,
+    /// What happens when a dependency is added again
+    pub duplicate_policy: DuplicatePolicy,
+    /// What happens at barriers with outstanding tasks
+    pub barrier_policy: BarrierPolicy,
+    /// How ready tasks are assigned to threads, unless given otherwise
+    pub scheduling_policy: SchedulingPolicy,
+    /// Number of barriers sent so far, new tasks belong to this phase
+    phase: u64,
     tasks: Vec<Rc<Task>>,
+    /// The same tasks as `tasks`, by their id
+    tasks_by_id: HashMap<u64, Rc<Task>>,
+    /// Commands for tasks depending on a task that is waited on, in the order they were given
+    held: Vec<Command>,
     functions: Vec<Rc<Function>>,
     task_id_count: u64,
 }
@@ -60,9 +116,21 @@ pub struct AppState {
 impl AppState {
     pub fn new() -> Self {
         AppState {
+            runtime_id: 0,
+            n_threads: 2,
             is_pre_init: false,
             is_init: false,
+            is_finished: false,
+            chaos: false,
+            allow_cycles: false,
+            removal_policy: RemovalPolicy::default(),
+            duplicate_policy: DuplicatePolicy::default(),
+            barrier_policy: BarrierPolicy::default(),
+            scheduling_policy: SchedulingPolicy::default(),
+            phase: 0,
             tasks: Vec::new(),
+            tasks_by_id: HashMap::new(),
+            held: Vec::new(),
             functions: Vec::new(),
             task_id_count: 0,
         }
@@ -97,6 +165,10 @@ impl AppState {
         }
     }
 
+    pub fn get_function(&self, id: u64) -> Option<&Rc<Function>> {
+        self.functions.get(id as usize)
+    }
+
     pub fn list_tasks(&self) {
         for t in &self.tasks {
             println!("{}", t);
@@ -104,19 +176,22 @@ impl AppState {
     }
 
     pub fn does_task_exist(&self, id: u64) -> bool {
-        self.tasks.iter().position(|t| t.id == id).is_some()
+        self.tasks_by_id.contains_key(&id)
+    }
+
+    /// Ids of all tasks, in the order they were created
+    pub fn task_ids(&self) -> Vec<u64> {
+        self.tasks.iter().map(|t| t.id).collect()
     }
 
     pub fn get_task(&self, id: u64) -> Option<&Rc<Task>> {
-        self.tasks.iter()
-            .position(|t| t.id == id)
-            .and_then(|idx| self.tasks.get(idx))
+        self.tasks_by_id.get(&id)
     }
 
     fn get_dependencies(&self) -> Vec<(u64, u64)> {
         let mut dependencies = Vec::new();
         for parent in &self.tasks {
-            for child_ptr in parent.children.borrow().iter() {
+            for child_ptr in parent.children.borrow().values() {
                 if let Some(child) = child_ptr.upgrade() {
                     dependencies.push((parent.id, child.id))
                 }
@@ -126,7 +201,40 @@ impl AppState {
         dependencies
     }
 
+    pub fn is_valid_thread_id(&self, thread_id: u64) -> bool {
+        thread_id < self.n_threads
+    }
+
+    /// The id the next task created by `create_task` will get
+    pub fn next_task_id(&self) -> u64 {
+        self.task_id_count
+    }
+
+    /// The number of tasks `create_task` can still create, before it runs out of ids
+    pub fn remaining_task_ids(&self) -> u64 {
+        // the count stops at the largest id, once a task with that id was created
+        match self.does_task_exist(u64::MAX) {
+            true => 0,
+            false => (u64::MAX - self.task_id_count).saturating_add(1),
+        }
+    }
+
     pub fn create_task(&mut self, is_critical: bool, function_id: Option<u64>, thread_id: u64) -> Result<Rc<Task>, &str> {
+        // create new id for task, 
+        let id = self.task_id_count;
+        self.create_task_with_id(id, is_critical, function_id, thread_id)
+    }
+
+    /// Creates a task with a user provided id.
+    /// Ids of tasks created afterwards by `create_task` will be larger than `id`.
+    pub fn create_task_with_id(&mut self, id: u64, is_critical: bool, function_id: Option<u64>, thread_id: u64) -> Result<Rc<Task>, &str> {
+        if self.does_task_exist(id) {
+            return Err("Task id already in use.");
+        }
+
+        if !self.is_valid_thread_id(thread_id) {
+            return Err("Thread id out of range.");
+        }
         
         // check if function for provided id exists
         let function = match function_id {
@@ -137,45 +245,274 @@ impl AppState {
             None => None,
         };
         
-        // create new id for task, 
-        let id = self.task_id_count;
-        self.task_id_count += 1;
+        self.task_id_count = self.task_id_count.max(id.saturating_add(1));
 
         let task = Rc::new(Task {
             id,
+            phase: self.phase,
             thread_id,
             function,
             is_critical,
-            parents: RefCell::new(Vec::new()),
-            children: RefCell::new(Vec::new()),
+            state: Cell::new(TaskState::Added),
+            waited_on: Cell::new(false),
+            parents: RefCell::new(BTreeMap::new()),
+            children: RefCell::new(BTreeMap::new()),
         });
 
         self.tasks.push(Rc::clone(&task));
+        self.tasks_by_id.insert(id, Rc::clone(&task));
         Ok(task)
     }
 
+    /// Moves the task with `task_id` to the state `to`, if the lifecycle allows it
+    pub fn transition_task(&self, task_id: u64, to: TaskState) -> Result<(), TransitionError> {
+        let task = self.get_task(task_id).ok_or(TransitionError::TaskNotFound(task_id))?;
+        let from = task.state.get();
+
+        if !from.can_become(to) {
+            return Err(TransitionError::Illegal { task_id, from, to });
+        }
+
+        task.state.set(to);
+        Ok(())
+    }
+
+    /// Moves the task with `task_id` to the state `to`, without checking the lifecycle.
+    /// Removed tasks are deleted.
+    pub fn force_task_state(&mut self, task_id: u64, to: TaskState) -> Option<()> {
+        match to {
+            TaskState::Removed => self.delete_task(task_id),
+            to => self.get_task(task_id).map(|t| t.state.set(to)),
+        }
+    }
+
+    /// Removes the task with `task_id`, handling its dependents as given by `policy`.
+    /// Returns the ids of all removed tasks, in the order they were removed.
+    pub fn remove_task(&mut self, task_id: u64, policy: RemovalPolicy) -> Result<Vec<u64>, RemovalError> {
+        let task = self.get_task(task_id).ok_or(RemovalError::TaskNotFound(task_id))?;
+        let dependents = task.live_children();
+
+        let removed = match policy {
+            RemovalPolicy::Refuse if !dependents.is_empty() => return Err(RemovalError::HasDependents { task_id, dependents }),
+            RemovalPolicy::Cascade => self.descendants(task_id),
+            _ => vec![task_id],
+        };
+
+        // make sure all tasks can be removed before removing any of them
+        for &id in &removed {
+            let from = self.get_task(id).map_or(TaskState::Removed, |t| t.state.get());
+            if !from.can_become(TaskState::Removed) {
+                return Err(RemovalError::IllegalTransition(TransitionError::Illegal { task_id: id, from, to: TaskState::Removed }));
+            }
+        }
+
+        for &id in &removed {
+            self.delete_task(id);
+        }
+
+        Ok(removed)
+    }
+
+    /// Deletes the task with `task_id` without any checks, detaching it from its dependencies
     pub fn delete_task(&mut self, task_id: u64) -> Option<()> {
+        let idx = self.tasks.iter().position(|t| t.id == task_id)?;
+        let task = self.tasks.remove(idx);
+        self.tasks_by_id.remove(&task_id);
+
+        // drop references to the deleted task, and to any other task that no longer exists
+        for t in &self.tasks {
+            for edges in [&t.parents, &t.children] {
+                edges.borrow_mut().retain(|_, w| w.strong_count() > 0 && !std::ptr::eq(w.as_ptr(), Rc::as_ptr(&task)));
+            }
+        }
+
+        Some(())
+    }
+
+    /// Returns the ids of the task with `task_id` and all its descendants, breadth first
+    fn descendants(&self, task_id: u64) -> Vec<u64> {
+        let mut descendants = vec![task_id];
+        let mut idx = 0;
+
+        while let Some(&id) = descendants.get(idx) {
+            for child in self.get_task(id).map(|t| t.live_children()).unwrap_or_default() {
+                if !descendants.contains(&child) {
+                    descendants.push(child);
+                }
+            }
+            idx += 1;
+        }
+
+        descendants
+    }
+
+    pub fn current_phase(&self) -> u64 {
+        self.phase
+    }
+
+    /// Ids of the tasks that have not finished running yet
+    pub fn outstanding_tasks(&self) -> Vec<u64> {
+        self.tasks.iter().filter(|t| !t.state.get().is_finished()).map(|t| t.id).collect()
+    }
 
+    /// Ids of the tasks that have not started yet, but could, as all tasks they depend on have finished
+    pub fn ready_tasks(&self) -> Vec<u64> {
         self.tasks.iter()
-            .position(|t| t.id == task_id)
-            .and_then(|idx| { self.tasks.remove(idx); Some(()) })
+            .filter(|t| matches!(t.state.get(), TaskState::Added | TaskState::Queued))
+            .filter(|t| t.parents.borrow().values().filter_map(Weak::upgrade).all(|p| p.state.get().is_finished()))
+            .map(|t| t.id)
+            .collect()
     }
 
-    pub fn add_dependency(&mut self, parent_id: u64, child_id: u64) -> Option<()> {
-        let parent = self.get_task(parent_id)?;
-        let child = self.get_task(child_id)?;
+    /// Ends the current phase, tasks created afterwards belong to the next one
+    pub fn barrier(&mut self) {
+        self.phase += 1;
+    }
+
+    /// Returns the task graph in the DOT format of graphviz, with the tasks of each phase in their own cluster
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tasks {\n");
+
+        for phase in 0..=self.phase {
+            let mut tasks = self.tasks.iter().filter(|t| t.phase == phase).peekable();
+            if tasks.peek().is_none() {
+                continue;
+            }
+
+            let _ = writeln!(dot, "    subgraph cluster_phase_{} {{\n        label = \"phase {}\";", phase, phase);
+            for t in tasks {
+                let label = t.function.as_ref().and_then(Weak::upgrade).map(|f| f.c_name().to_string_lossy().replace('"', "\\\""));
+                let _ = write!(dot, "        {} [label=\"{}", t.id, t.id);
+                if let Some(label) = label {
+                    let _ = write!(dot, ": {}", label);
+                }
+                let _ = writeln!(dot, "\"{}];", if t.is_critical { ", penwidth=2" } else { "" });
+            }
+            dot += "    }\n";
+        }
+
+        for (parent, child) in self.get_dependencies() {
+            let _ = writeln!(dot, "    {} -> {};", parent, child);
+        }
+
+        dot += "}\n";
+        dot
+    }
+
+    /// Ids of the tasks depending on the task with `task_id`
+    pub fn get_children(&self, task_id: u64) -> Option<Vec<u64>> {
+        self.get_task(task_id).map(|t| t.live_children())
+    }
+
+    /// Marks the task with `task_id` as waited on, until it has finished running
+    pub fn wait_on(&self, task_id: u64) -> Option<()> {
+        self.get_task(task_id).map(|t| t.waited_on.set(true))
+    }
+
+    /// Returns the id of a task that is waited on and has not finished yet,
+    /// which the task with `task_id` depends on directly or indirectly
+    pub fn blocking_wait(&self, task_id: u64) -> Option<u64> {
+        self.tasks.iter()
+            .filter(|t| t.is_waited_on() && t.id != task_id)
+            .find(|t| self.find_path(t.id, task_id).is_some())
+            .map(|t| t.id)
+    }
+
+    /// Holds back `command` until `release_held` returns it
+    pub fn hold(&mut self, command: Command) {
+        self.held.push(command);
+    }
+
+    /// Removes and returns all held commands for tasks that are no longer blocked by a wait,
+    /// in the order they were held
+    pub fn release_held(&mut self) -> Vec<Command> {
+        let (released, held) = std::mem::take(&mut self.held)
+            .into_iter()
+            .partition(|command| command.task_id().is_none_or(|id| self.blocking_wait(id).is_none()));
+        self.held = held;
+        released
+    }
+
+    /// Removes and returns all held commands, whether they are still blocked or not
+    pub fn discard_held(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.held)
+    }
+
+    /// Adds a dependency from `parent_id` to `child_id`.
+    /// Dependencies that already exist are refused, as are dependencies that would close a cycle, unless `allow_cycles` is set.
+    pub fn add_dependency(&mut self, parent_id: u64, child_id: u64) -> Result<(), DependencyError> {
+        self.link(parent_id, child_id, !self.allow_cycles)
+    }
+
+    /// Adds a dependency like `add_dependency`, but without looking for cycles.
+    /// For dependencies that cannot close one, like those of generated graphs, which always go from earlier to later new tasks.
+    pub(crate) fn add_acyclic_dependency(&mut self, parent_id: u64, child_id: u64) -> Result<(), DependencyError> {
+        self.link(parent_id, child_id, false)
+    }
+
+    fn link(&mut self, parent_id: u64, child_id: u64, check_cycles: bool) -> Result<(), DependencyError> {
+        let parent = self.get_task(parent_id).ok_or(DependencyError::TaskNotFound(parent_id))?;
+        let child = self.get_task(child_id).ok_or(DependencyError::TaskNotFound(child_id))?;
+
+        if parent.children.borrow().contains_key(&child_id) {
+            return Err(DependencyError::Duplicate { parent_id, child_id });
+        }
+
+        if check_cycles {
+            if let Some(path) = self.find_path(child_id, parent_id) {
+                return Err(DependencyError::Cycle([parent_id].into_iter().chain(path).collect()));
+            }
+        }
 
         {
             let mut children: RefMut<_> = parent.children.borrow_mut();
-            children.push(Rc::downgrade(child));
+            children.insert(child_id, Rc::downgrade(child));
         }
 
         {
             let mut parents: RefMut<_> = child.parents.borrow_mut();
-            parents.push(Rc::downgrade(parent));
+            parents.insert(parent_id, Rc::downgrade(parent));
         }
 
-        Some(())
+        Ok(())
+    }
+
+    /// Removes the dependency from `parent_id` to `child_id`
+    pub fn remove_dependency(&mut self, parent_id: u64, child_id: u64) -> Result<(), DependencyError> {
+        let parent = self.get_task(parent_id).ok_or(DependencyError::TaskNotFound(parent_id))?;
+        let child = self.get_task(child_id).ok_or(DependencyError::TaskNotFound(child_id))?;
+
+        parent.children.borrow_mut().remove(&child_id).ok_or(DependencyError::NotFound { parent_id, child_id })?;
+        child.parents.borrow_mut().remove(&parent_id);
+
+        Ok(())
+    }
+
+    /// Returns the ids of the tasks on the shortest path from `from` to `to`, following dependencies to children
+    fn find_path(&self, from: u64, to: u64) -> Option<Vec<u64>> {
+        // the task each visited task was reached from
+        let mut previous = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(id) = queue.pop_front() {
+            if id == to {
+                let mut path = vec![to];
+                while let Some(&Some(prev)) = previous.get(&path[path.len() - 1]) {
+                    path.push(prev);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for child in self.get_task(id).map(|t| t.live_children()).unwrap_or_default() {
+                if let Entry::Vacant(entry) = previous.entry(child) {
+                    entry.insert(Some(id));
+                    queue.push_back(child);
+                }
+            }
+        }
+
+        None
     }
 }
 
@@ -188,8 +525,14 @@ impl Default for AppState {
 impl Display for AppState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut task_string = String::new();
-        for t in &self.tasks {
-            let _ = write!(task_string, "\n\t\t{}", t);
+        for phase in 0..=self.phase {
+            let mut tasks = self.tasks.iter().filter(|t| t.phase == phase).peekable();
+            if tasks.peek().is_some() {
+                let _ = write!(task_string, "\n\t\tPhase {}:", phase);
+            }
+            for t in tasks {
+                let _ = write!(task_string, "\n\t\t\t{}", t);
+            }
         }
 
         let mut function_string = String::new();
@@ -202,18 +545,25 @@ impl Display for AppState {
             let _ = write!(dependencies_string, "\n\t\t(P: {}, C: {})", d.0, d.1);
         }
 
-        write!(f, "Current State:\n\tPreInitialized: {}\n\tInitialized: {}\n\tTasks: {}\n\tFunctions/Labels: {}\n\tDependencies: {}", self.is_pre_init, self.is_init, task_string, function_string, dependencies_string)
+        write!(f, "Current State:\n\tPreInitialized: {}\n\tInitialized: {}\n\tFinished: {}\n\tPhase: {}\n\tTasks: {}\n\tFunctions/Labels: {}\n\tDependencies: {}", self.is_pre_init, self.is_init, self.is_finished, self.phase, task_string, function_string, dependencies_string)
     }
 }
 
 #[derive(Debug)]
 pub struct Task {
     id: u64,
+    /// Number of barriers sent before the task was created
+    phase: u64,
     thread_id: u64,
     function: Option<Weak<Function>>,
     is_critical: bool,
-    parents: RefCell<Vec<Weak<Task>>>,
-    children: RefCell<Vec<Weak<Task>>>,
+    state: Cell<TaskState>,
+    /// A waiton was sent for this task
+    waited_on: Cell<bool>,
+    /// Tasks this one depends on, by their id
+    parents: RefCell<BTreeMap<u64, Weak<Task>>>,
+    /// Tasks depending on this one, by their id
+    children: RefCell<BTreeMap<u64, Weak<Task>>>,
 }
 
 impl Task {
@@ -229,6 +579,33 @@ impl Task {
     pub fn get_id(&self) -> u64 {
         self.id
     }
+
+    pub fn get_phase(&self) -> u64 {
+        self.phase
+    }
+
+    pub fn get_state(&self) -> TaskState {
+        self.state.get()
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.is_critical
+    }
+
+    /// The function the task is labelled with, if it has one and it still exists
+    pub fn get_function(&self) -> Option<Rc<Function>> {
+        self.function.as_ref().and_then(Weak::upgrade)
+    }
+
+    /// True if a waiton was sent for this task and it has not finished yet
+    pub fn is_waited_on(&self) -> bool {
+        self.waited_on.get() && !self.state.get().is_finished()
+    }
+
+    /// Ids of the tasks depending on this one which still exist
+    fn live_children(&self) -> Vec<u64> {
+        self.children.borrow().values().filter_map(Weak::upgrade).map(|c| c.id).collect()
+    }
 }
 
 impl Display for Task {
@@ -238,7 +615,10 @@ impl Display for Task {
                             .and_then(|f| f.upgrade())
                             .map_or("None".to_string(), |f| f.name.clone());
 
-        let string = format!("{}: label = {}, is_critical = {}, thread_id = {}", self.id, f_label, self.is_critical, self.thread_id);
+        let mut string = format!("{}: label = {}, is_critical = {}, thread_id = {}, state = {}", self.id, f_label, self.is_critical, self.thread_id, self.state.get());
+        if self.is_waited_on() {
+            string += ", waited on";
+        }
         write!(f, "{}", string)
     }
 }
@@ -249,11 +629,14 @@ impl From<u64> for Task {
     fn from(id: u64) -> Self {
         Task {
             id,
+            phase: 0,
             thread_id: 0,
             function: Some(Rc::downgrade(&Rc::new(0.into()))),
             is_critical: false,
-            parents: RefCell::new(Vec::new()),
-            children: RefCell::new(Vec::new()),
+            state: Cell::new(TaskState::Added),
+            waited_on: Cell::new(false),
+            parents: RefCell::new(BTreeMap::new()),
+            children: RefCell::new(BTreeMap::new()),
         }
     }
 }
@@ -271,6 +654,10 @@ impl Function {
         if !name.is_ascii() {
             return Err("string contains non ascii characters");
         }
+
+        if name.contains('\0') {
+            return Err("string contains null bytes");
+        }
         
         // add null byte for c string
         name += "\0";
@@ -281,6 +668,11 @@ impl Function {
     pub fn into_raw_parts(&self) -> (u64, *mut c_char) {
         (self.id, self.name.as_ptr() as *mut c_char)
     }
+
+    /// Returns the name as it is sent to ayudame, i.e. null terminated
+    pub fn c_name(&self) -> &CStr {
+        CStr::from_bytes_with_nul(self.name.as_bytes()).expect("function names are null terminated")
+    }
 }
 
 impl Display for Function {
@@ -305,8 +697,10 @@ impl From<u64> for Function {
 // impl_ord!(Task; Function);
 
 #[cfg(test)]
+// the baseline tests look up the first function with `get(0)`, like the others by their index
+#[allow(clippy::get_first)]
 mod tests {
-    use crate::{AppState, Function};
+    use crate::{AppState, Command, DependencyError, Function, RemovalError, RemovalPolicy, TaskState, TransitionError};
 
     #[test]
     fn function_new_is_ok() {
@@ -320,6 +714,21 @@ mod tests {
         assert!(f.is_err());
     }
 
+    #[test]
+    fn function_new_rejects_null_bytes() {
+        let f = Function::new(0, String::from("func\0tion"));
+        assert!(f.is_err());
+    }
+
+    #[test]
+    fn function_c_name() {
+        let f = Function::new(0, String::from("function0")).unwrap();
+        assert_eq!(f.c_name().to_str(), Ok("function0"));
+
+        let f: Function = 3.into();
+        assert_eq!(f.c_name().to_str(), Ok("default_function_3"));
+    }
+
     #[test]
     fn function_prepare_for_sending() {
         let f = Function::new(0, String::from("function0")).unwrap();
@@ -373,4 +782,253 @@ mod tests {
 
         assert!(state.create_task(false, Some(0), 0).is_ok());
     }
+
+    #[test]
+    fn app_state_create_task_checks_thread_id() {
+        let mut state = AppState::new();
+        state.n_threads = 4;
+
+        assert!(state.create_task(false, None, 3).is_ok());
+        assert!(state.create_task(false, None, 4).is_err());
+    }
+
+    #[test]
+    fn app_state_transition_task() {
+        let mut state = AppState::new();
+        let id = state.create_task(false, None, 0).unwrap().get_id();
+        assert_eq!(state.get_task(id).unwrap().get_state(), TaskState::Added);
+
+        assert_eq!(state.transition_task(id, TaskState::Running), Err(TransitionError::Illegal { task_id: id, from: TaskState::Added, to: TaskState::Running }));
+
+        for next in [TaskState::Queued, TaskState::PreRun, TaskState::Running, TaskState::PostRun] {
+            assert_eq!(state.transition_task(id, next), Ok(()));
+            assert_eq!(state.get_task(id).unwrap().get_state(), next);
+        }
+
+        assert!(state.transition_task(id, TaskState::PostRun).is_err());
+        assert_eq!(state.transition_task(7, TaskState::Queued), Err(TransitionError::TaskNotFound(7)));
+    }
+
+    #[test]
+    fn app_state_create_task_with_id() {
+        let mut state = AppState::new();
+
+        assert!(state.create_task_with_id(5, false, None, 0).is_ok());
+        assert!(state.create_task_with_id(5, false, None, 0).is_err());
+        assert_eq!(state.next_task_id(), 6);
+
+        assert!(state.create_task_with_id(2, false, None, 0).is_ok());
+        assert_eq!(state.create_task(false, None, 0).unwrap().get_id(), 6);
+    }
+
+    #[test]
+    fn app_state_remaining_task_ids() {
+        let mut state = AppState::new();
+        assert_eq!(state.remaining_task_ids(), u64::MAX);
+
+        state.create_task_with_id(u64::MAX - 1, false, None, 0).unwrap();
+        assert_eq!(state.remaining_task_ids(), 1);
+        state.create_task(false, None, 0).unwrap();
+        assert_eq!(state.remaining_task_ids(), 0);
+        assert!(state.create_task(false, None, 0).is_err());
+    }
+
+    #[test]
+    fn app_state_deleted_task_ids_can_be_reused() {
+        let mut state = AppState::new();
+        state.create_task_with_id(5, false, None, 0).unwrap();
+        state.delete_task(5).unwrap();
+        assert!(!state.does_task_exist(5));
+        assert!(state.get_task(5).is_none());
+
+        state.create_task_with_id(5, true, None, 1).unwrap();
+        assert!(state.get_task(5).unwrap().is_critical());
+        assert_eq!(state.task_ids(), [5]);
+    }
+
+    #[test]
+    fn app_state_add_acyclic_dependency() {
+        let mut state = diamond();
+        // not looking for cycles is up to the caller, but duplicates are still refused
+        assert!(state.add_acyclic_dependency(3, 0).is_ok());
+        assert_eq!(state.add_acyclic_dependency(3, 0), Err(DependencyError::Duplicate { parent_id: 3, child_id: 0 }));
+        assert_eq!(state.add_acyclic_dependency(3, 9), Err(DependencyError::TaskNotFound(9)));
+    }
+
+    #[test]
+    fn app_state_force_task_state() {
+        let mut state = AppState::new();
+        let id = state.create_task(false, None, 0).unwrap().get_id();
+
+        assert_eq!(state.force_task_state(id, TaskState::Running), Some(()));
+        assert_eq!(state.get_task(id).unwrap().get_state(), TaskState::Running);
+
+        assert_eq!(state.force_task_state(id, TaskState::Removed), Some(()));
+        assert!(!state.does_task_exist(id));
+        assert_eq!(state.force_task_state(id, TaskState::Queued), None);
+    }
+
+    #[test]
+    fn app_state_add_dependency_rejects_cycles() {
+        let mut state = AppState::new();
+        for _ in 0..4 {
+            state.create_task(false, None, 0).unwrap();
+        }
+
+        assert_eq!(state.add_dependency(0, 1), Ok(()));
+        assert_eq!(state.add_dependency(1, 2), Ok(()));
+        assert_eq!(state.add_dependency(0, 2), Ok(()));
+        assert_eq!(state.add_dependency(2, 0), Err(DependencyError::Cycle(vec![2, 0, 2])));
+        assert_eq!(state.add_dependency(1, 1), Err(DependencyError::Cycle(vec![1, 1])));
+        assert_eq!(state.add_dependency(2, 7), Err(DependencyError::TaskNotFound(7)));
+
+        assert_eq!(state.add_dependency(2, 3), Ok(()));
+        assert_eq!(state.add_dependency(3, 1), Err(DependencyError::Cycle(vec![3, 1, 2, 3])));
+        assert_eq!(state.get_dependencies(), [(0, 1), (0, 2), (1, 2), (2, 3)]);
+        assert_eq!(state.add_dependency(0, 1), Err(DependencyError::Duplicate { parent_id: 0, child_id: 1 }));
+
+        state.allow_cycles = true;
+        assert_eq!(state.add_dependency(3, 1), Ok(()));
+    }
+
+    fn diamond() -> AppState {
+        let mut state = AppState::new();
+        for _ in 0..4 {
+            state.create_task(false, None, 0).unwrap();
+        }
+        for (parent, child) in [(0, 1), (0, 2), (1, 3), (2, 3)] {
+            state.add_dependency(parent, child).unwrap();
+        }
+        state
+    }
+
+    #[test]
+    fn app_state_remove_task_refuse() {
+        let mut state = diamond();
+
+        assert_eq!(state.remove_task(1, RemovalPolicy::Refuse), Err(RemovalError::HasDependents { task_id: 1, dependents: vec![3] }));
+        assert_eq!(state.remove_task(3, RemovalPolicy::Refuse), Ok(vec![3]));
+        assert_eq!(state.remove_task(1, RemovalPolicy::Refuse), Ok(vec![1]));
+        assert_eq!(state.remove_task(7, RemovalPolicy::Refuse), Err(RemovalError::TaskNotFound(7)));
+    }
+
+    #[test]
+    fn app_state_remove_task_cascade() {
+        let mut state = diamond();
+        state.force_task_state(3, TaskState::Running);
+
+        assert_eq!(state.remove_task(1, RemovalPolicy::Cascade), Err(RemovalError::IllegalTransition(TransitionError::Illegal { task_id: 3, from: TaskState::Running, to: TaskState::Removed })));
+        assert!(state.does_task_exist(1));
+
+        state.force_task_state(3, TaskState::PostRun);
+        assert_eq!(state.remove_task(0, RemovalPolicy::Cascade), Ok(vec![0, 1, 2, 3]));
+        assert!(state.tasks.is_empty());
+    }
+
+    #[test]
+    fn app_state_remove_task_detach() {
+        let mut state = diamond();
+
+        assert_eq!(state.remove_task(1, RemovalPolicy::Detach), Ok(vec![1]));
+        assert_eq!(state.get_dependencies(), [(0, 2), (2, 3)]);
+
+        // no references to removed tasks are left behind
+        for task in &state.tasks {
+            assert!(task.parents.borrow().values().chain(task.children.borrow().values()).all(|w| w.upgrade().is_some()));
+        }
+        assert_eq!(state.get_task(3).unwrap().parents.borrow().len(), 1);
+    }
+
+    #[test]
+    fn app_state_remove_dependency() {
+        let mut state = diamond();
+
+        assert_eq!(state.remove_dependency(1, 3), Ok(()));
+        assert_eq!(state.get_dependencies(), [(0, 1), (0, 2), (2, 3)]);
+        assert_eq!(state.get_task(3).unwrap().parents.borrow().len(), 1);
+
+        assert_eq!(state.remove_dependency(1, 3), Err(DependencyError::NotFound { parent_id: 1, child_id: 3 }));
+        assert_eq!(state.remove_dependency(1, 7), Err(DependencyError::TaskNotFound(7)));
+
+        // 1 has no dependents left
+        assert_eq!(state.remove_task(1, RemovalPolicy::Refuse), Ok(vec![1]));
+    }
+
+    #[test]
+    fn app_state_wait_on_holds_back_dependents() {
+        let mut state = diamond();
+        let extra = state.create_task(false, None, 0).unwrap().get_id();
+
+        assert_eq!(state.wait_on(1), Some(()));
+        assert_eq!(state.wait_on(7), None);
+        assert_eq!(state.blocking_wait(3), Some(1));
+        assert_eq!(state.blocking_wait(1), None);
+        assert_eq!(state.blocking_wait(2), None);
+        assert_eq!(state.blocking_wait(extra), None);
+
+        state.hold(Command::AddTaskToQueue { task_id: 3 });
+        state.hold(Command::RunTask { task_id: 3 });
+        assert!(state.release_held().is_empty());
+
+        state.force_task_state(1, TaskState::PostRun);
+        assert_eq!(state.blocking_wait(3), None);
+        assert_eq!(state.release_held(), [Command::AddTaskToQueue { task_id: 3 }, Command::RunTask { task_id: 3 }]);
+
+        state.wait_on(2);
+        state.hold(Command::PreRunTask { task_id: 3 });
+        assert_eq!(state.discard_held(), [Command::PreRunTask { task_id: 3 }]);
+        assert!(state.release_held().is_empty());
+        assert_eq!(state.task_ids(), [0, 1, 2, 3, extra]);
+    }
+
+    #[test]
+    fn app_state_ready_tasks() {
+        let mut state = AppState::new();
+        for _ in 0..4 {
+            state.create_task(false, None, 0).unwrap();
+        }
+        state.add_dependency(0, 1).unwrap();
+        state.add_dependency(1, 2).unwrap();
+        state.transition_task(3, TaskState::Queued).unwrap();
+        assert_eq!(state.ready_tasks(), [0, 3]);
+
+        state.force_task_state(0, TaskState::PostRun);
+        state.force_task_state(3, TaskState::Running);
+        assert_eq!(state.ready_tasks(), [1]);
+
+        // removed parents do not hold back their children
+        state.remove_task(1, RemovalPolicy::Detach).unwrap();
+        assert_eq!(state.ready_tasks(), [2]);
+    }
+
+    #[test]
+    fn app_state_barrier_phases() {
+        let mut state = AppState::new();
+        state.create_function("kernel".to_string());
+        state.create_task(true, Some(0), 0).unwrap();
+        state.force_task_state(0, TaskState::PostRun);
+        state.create_task(false, None, 0).unwrap();
+        assert_eq!(state.outstanding_tasks(), [1]);
+
+        state.barrier();
+        assert_eq!(state.current_phase(), 1);
+        let task = state.create_task(false, None, 0).unwrap();
+        assert_eq!(task.get_phase(), 1);
+        state.add_dependency(1, 2).unwrap();
+
+        assert_eq!(state.to_dot(), "\
+digraph tasks {
+    subgraph cluster_phase_0 {
+        label = \"phase 0\";
+        0 [label=\"0: kernel\", penwidth=2];
+        1 [label=\"1\"];
+    }
+    subgraph cluster_phase_1 {
+        label = \"phase 1\";
+        2 [label=\"2\"];
+    }
+    1 -> 2;
+}
+");
+    }
 }
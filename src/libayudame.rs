@@ -0,0 +1,256 @@
+use std::{
+    env,
+    ffi::{CStr, OsString},
+    fmt::Display,
+    os::raw::c_char,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use libloading::Library;
+
+use crate::{event_sink::SyncEventSink, EventSink};
+
+/// Environment variable that can point to the ayudame library
+pub const AYUDAME_LIB_ENV: &str = "AYUDAME_LIB";
+
+type U64Fn = unsafe extern "C" fn(u64);
+type U64U64Fn = unsafe extern "C" fn(u64, u64);
+type U64x4Fn = unsafe extern "C" fn(u64, u64, u64, u64);
+type RegisterFunctionFn = unsafe extern "C" fn(u64, *mut c_char);
+type VoidFn = unsafe extern "C" fn();
+
+/// Where the path of the library that was loaded came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibrarySource {
+    Argument,
+    Environment,
+    SearchPath,
+}
+
+impl Display for LibrarySource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LibrarySource::Argument => write!(f, "set via --ayudame-lib"),
+            LibrarySource::Environment => write!(f, "set via {}", AYUDAME_LIB_ENV),
+            LibrarySource::SearchPath => write!(f, "looked up in the library search path"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    LibraryNotFound { path: PathBuf, source: LibrarySource, reason: libloading::Error },
+    MissingSymbol { path: PathBuf, symbol: &'static str },
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::LibraryNotFound { path, source, reason } =>
+                write!(f, "Unable to load ayudame library {} ({}):\n\t{}", path.display(), source, reason),
+            LoadError::MissingSymbol { path, symbol } =>
+                write!(f, "Symbol {} not found in ayudame library {}", symbol, path.display()),
+        }
+    }
+}
+
+impl std::error::Error for LoadError { }
+
+/// Sends every event to a libayudame that was loaded at runtime
+pub struct LibAyudameSink {
+    path: PathBuf,
+    preinit: U64Fn,
+    init: U64Fn,
+    addtask: U64x4Fn,
+    registerfunction: RegisterFunctionFn,
+    adddependency: U64x4Fn,
+    addtasktoqueue: U64U64Fn,
+    preruntask: U64U64Fn,
+    runtask: U64Fn,
+    postruntask: U64Fn,
+    removetask: U64Fn,
+    barrier: VoidFn,
+    waiton: U64Fn,
+    finish: VoidFn,
+    // needs to outlive the function pointers above, and those handed out by `sync_sink`
+    library: Arc<Library>,
+}
+
+/// The lifecycle events of a loaded libayudame, which worker threads call without any locking
+struct LibAyudameLifecycle {
+    addtasktoqueue: U64U64Fn,
+    preruntask: U64U64Fn,
+    runtask: U64Fn,
+    postruntask: U64Fn,
+    removetask: U64Fn,
+    _library: Arc<Library>,
+}
+
+impl LibAyudameSink {
+    /// Loads libayudame from the first location that is set:
+    /// `path` (the command line argument), the `AYUDAME_LIB` environment variable,
+    /// or the platform specific library name, which is resolved through the usual search path.
+    pub fn locate(path: Option<PathBuf>) -> Result<Self, LoadError> {
+        let (path, source) = match (path, env::var_os(AYUDAME_LIB_ENV)) {
+            (Some(path), _) => (path, LibrarySource::Argument),
+            (None, Some(path)) if !path.is_empty() => (path.into(), LibrarySource::Environment),
+            _ => (PathBuf::from(libloading::library_filename("ayudame")), LibrarySource::SearchPath),
+        };
+
+        Self::load(path, source)
+    }
+
+    /// Loads libayudame from `path` and resolves all ayu_event_* symbols
+    pub fn load(path: PathBuf, source: LibrarySource) -> Result<Self, LoadError> {
+        let library = unsafe { Library::new(OsString::from(&path)) }
+            .map_err(|reason| LoadError::LibraryNotFound { path: path.clone(), source, reason })?;
+
+        macro_rules! symbol {
+            ($name:literal) => {
+                unsafe {
+                    *library.get(concat!($name, "\0").as_bytes())
+                        .map_err(|_| LoadError::MissingSymbol { path: path.clone(), symbol: $name })?
+                }
+            };
+        }
+
+        Ok(Self {
+            preinit: symbol!("ayu_event_preinit"),
+            init: symbol!("ayu_event_init"),
+            addtask: symbol!("ayu_event_addtask"),
+            registerfunction: symbol!("ayu_event_registerfunction"),
+            adddependency: symbol!("ayu_event_adddependency"),
+            addtasktoqueue: symbol!("ayu_event_addtasktoqueue"),
+            preruntask: symbol!("ayu_event_preruntask"),
+            runtask: symbol!("ayu_event_runtask"),
+            postruntask: symbol!("ayu_event_postruntask"),
+            removetask: symbol!("ayu_event_removetask"),
+            barrier: symbol!("ayu_event_barrier"),
+            waiton: symbol!("ayu_event_waiton"),
+            finish: symbol!("ayu_event_finish"),
+            path,
+            library: Arc::new(library),
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+impl EventSink for LibAyudameSink {
+    fn pre_init(&mut self, rt: u64) {
+        unsafe { (self.preinit)(rt) }
+    }
+
+    fn init(&mut self, n_threads: u64) {
+        unsafe { (self.init)(n_threads) }
+    }
+
+    fn add_task(&mut self, task_id: u64, func_id: u64, priority: u64, scope_id: u64) {
+        unsafe { (self.addtask)(task_id, func_id, priority, scope_id) }
+    }
+
+    fn register_function(&mut self, func_id: u64, name: &CStr) {
+        // ayudame does not write to name, the pointer is only mut because of the C signature
+        unsafe { (self.registerfunction)(func_id, name.as_ptr() as *mut c_char) }
+    }
+
+    fn add_dependency(&mut self, to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64) {
+        unsafe { (self.adddependency)(to_id, from_id, memaddr, orig_memaddr) }
+    }
+
+    fn add_task_to_queue(&mut self, task_id: u64, thread_id: u64) {
+        unsafe { (self.addtasktoqueue)(task_id, thread_id) }
+    }
+
+    fn pre_run_task(&mut self, task_id: u64, thread_id: u64) {
+        unsafe { (self.preruntask)(task_id, thread_id) }
+    }
+
+    fn run_task(&mut self, task_id: u64) {
+        unsafe { (self.runtask)(task_id) }
+    }
+
+    fn post_run_task(&mut self, task_id: u64) {
+        unsafe { (self.postruntask)(task_id) }
+    }
+
+    fn remove_task(&mut self, task_id: u64) {
+        unsafe { (self.removetask)(task_id) }
+    }
+
+    fn barrier(&mut self) {
+        unsafe { (self.barrier)() }
+    }
+
+    fn wait_on(&mut self, task_id: u64) {
+        unsafe { (self.waiton)(task_id) }
+    }
+
+    fn finish(&mut self) {
+        unsafe { (self.finish)() }
+    }
+
+    fn sync_sink(&self) -> Option<Arc<dyn SyncEventSink>> {
+        Some(Arc::new(LibAyudameLifecycle {
+            addtasktoqueue: self.addtasktoqueue,
+            preruntask: self.preruntask,
+            runtask: self.runtask,
+            postruntask: self.postruntask,
+            removetask: self.removetask,
+            _library: Arc::clone(&self.library),
+        }))
+    }
+}
+
+impl SyncEventSink for LibAyudameLifecycle {
+    fn add_task_to_queue(&self, task_id: u64, thread_id: u64) {
+        unsafe { (self.addtasktoqueue)(task_id, thread_id) }
+    }
+
+    fn pre_run_task(&self, task_id: u64, thread_id: u64) {
+        unsafe { (self.preruntask)(task_id, thread_id) }
+    }
+
+    fn run_task(&self, task_id: u64) {
+        unsafe { (self.runtask)(task_id) }
+    }
+
+    fn post_run_task(&self, task_id: u64) {
+        unsafe { (self.postruntask)(task_id) }
+    }
+
+    fn remove_task(&self, task_id: u64) {
+        unsafe { (self.removetask)(task_id) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{LibAyudameSink, LibrarySource, LoadError};
+
+    #[test]
+    fn load_reports_missing_file() {
+        let path = PathBuf::from("/does/not/exist/libayudame.so");
+        match LibAyudameSink::load(path.clone(), LibrarySource::Argument) {
+            Err(LoadError::LibraryNotFound { path: p, source, .. }) => {
+                assert_eq!(p, path);
+                assert_eq!(source, LibrarySource::Argument);
+            },
+            _ => panic!("expected LibraryNotFound"),
+        }
+    }
+
+    #[test]
+    fn locate_prefers_argument() {
+        let path = PathBuf::from("/does/not/exist/libayudame.so");
+        match LibAyudameSink::locate(Some(path)) {
+            Err(LoadError::LibraryNotFound { source, .. }) => assert_eq!(source, LibrarySource::Argument),
+            _ => panic!("expected LibraryNotFound"),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::command::ParseError;
+
+/// Where a task is in its lifecycle, as far as the events sent for it are concerned.
+///
+/// A task moves through the states in the order they are declared,
+/// except that it can be removed right after it was added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TaskState {
+    Added,
+    Queued,
+    PreRun,
+    Running,
+    PostRun,
+    Removed,
+}
+
+impl TaskState {
+    /// States a task in this state may move to next
+    pub fn successors(self) -> &'static [TaskState] {
+        match self {
+            TaskState::Added => &[TaskState::Queued, TaskState::Removed],
+            TaskState::Queued => &[TaskState::PreRun],
+            TaskState::PreRun => &[TaskState::Running],
+            TaskState::Running => &[TaskState::PostRun],
+            TaskState::PostRun => &[TaskState::Removed],
+            TaskState::Removed => &[],
+        }
+    }
+
+    pub fn can_become(self, next: TaskState) -> bool {
+        self.successors().contains(&next)
+    }
+
+    /// True once the task has finished running
+    pub fn is_finished(self) -> bool {
+        self >= TaskState::PostRun
+    }
+}
+
+impl Display for TaskState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TaskState::Added => "added",
+            TaskState::Queued => "queued",
+            TaskState::PreRun => "pre-run",
+            TaskState::Running => "running",
+            TaskState::PostRun => "post-run",
+            TaskState::Removed => "removed",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What happens at a barrier when tasks created before it have not finished yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BarrierPolicy {
+    /// Only list the outstanding tasks
+    #[default]
+    Report,
+    /// Warn about the outstanding tasks
+    Warn,
+    /// Refuse the barrier
+    Refuse,
+}
+
+impl FromStr for BarrierPolicy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "report" => Ok(BarrierPolicy::Report),
+            "warn" => Ok(BarrierPolicy::Warn),
+            "refuse" => Ok(BarrierPolicy::Refuse),
+            _ => Err(ParseError::InvalidArgument(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TransitionError {
+    TaskNotFound(u64),
+    Illegal { task_id: u64, from: TaskState, to: TaskState },
+}
+
+impl Display for TransitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransitionError::TaskNotFound(id) => write!(f, "Task with id: {} not found.", id),
+            TransitionError::Illegal { task_id, from, to } => {
+                let allowed: Vec<_> = from.successors().iter().map(|s| s.to_string()).collect();
+                write!(f, "Task {} cannot go from {} to {}", task_id, from, to)?;
+                match allowed.is_empty() {
+                    true => write!(f, "."),
+                    false => write!(f, ", it can only become {}.", allowed.join(" or ")),
+                }
+            },
+        }
+    }
+}
+
+impl std::error::Error for TransitionError { }
+
+#[cfg(test)]
+mod tests {
+    use super::{BarrierPolicy, TaskState, TransitionError};
+
+    #[test]
+    fn task_state_order() {
+        let order = [TaskState::Added, TaskState::Queued, TaskState::PreRun, TaskState::Running, TaskState::PostRun, TaskState::Removed];
+        for pair in order.windows(2) {
+            assert!(pair[0].can_become(pair[1]));
+            assert!(!pair[1].can_become(pair[0]));
+        }
+
+        assert!(TaskState::Added.can_become(TaskState::Removed));
+        assert!(!TaskState::Running.can_become(TaskState::Removed));
+        assert!(!TaskState::PostRun.can_become(TaskState::PostRun));
+    }
+
+    #[test]
+    fn transition_error_display() {
+        let e = TransitionError::Illegal { task_id: 3, from: TaskState::Added, to: TaskState::Running };
+        assert_eq!(e.to_string(), "Task 3 cannot go from added to running, it can only become queued or removed.");
+    }
+
+    #[test]
+    fn barrier_policy_parse() {
+        assert_eq!("refuse".parse(), Ok(BarrierPolicy::Refuse));
+        assert!("wait".parse::<BarrierPolicy>().is_err());
+    }
+}
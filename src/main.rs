@@ -1,98 +1,370 @@
-/// A debugging application to test out ayu_events functions
+//! A debugging application to test out ayu_events functions
 
 // Should be able to send any event to ayudame at any time,
 // CLI app
 // create task ids, function ids, etc with counters
 
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::fmt::Display;
-use std::{io, convert::TryFrom};
-
-use ayudame_wrapper::{InputTypes, AppState};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+use std::{io, thread, convert::TryFrom};
+
+use ayudame_wrapper::{InputTypes, AppState, BarrierPolicy, Command, DependencyError, DuplicatePolicy, Durations, EventSink, Execution, Generator, Options, PrintSink, RecordingSink, RemovalError, RemovalPolicy, SchedulingPolicy, TaskState, TransitionError, MAX_THREADS};
+use ayudame_wrapper::dependency::memory_addresses;
+use ayudame_wrapper::generator::{read_matrix_market, EliminationOrder, MatrixError, SymbolicFactorization};
+use ayudame_wrapper::command::{missing_arguments, ParseError, WaitTarget, COMMAND_SYNTAX};
+use ayudame_wrapper::event_log::{read_log, replay, LogError};
+use ayudame_wrapper::executor;
+#[cfg(feature = "libayudame")]
+use ayudame_wrapper::LibAyudameSink;
+use ayudame_wrapper::options::{config_arguments, Startup, USAGE};
+use ayudame_wrapper::scheduler::schedule;
+use ayudame_wrapper::simulator::simulate;
 use ayudame_wrapper::helper_macros::match_or_continue;
 
 const PARSE_UNSIGNED_ERROR_MSG: &str = "Invalid input, must be positive numeric";
 
 type Result<T> = std::result::Result<T, UserInputError>;
 
-enum Command {
+/// Why a session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shutdown {
+    Quit,
+    EndOfInput,
+    /// SIGINT or SIGTERM was received
+    Interrupted,
+}
+
+enum MenuOption {
     AddTask,
     PrintState,
+    Help,
+    Command(String),
 }
 
 #[derive(Debug)]
 enum UserInputError {
     TaskIdNotFound(u64),
+    DuplicateTaskId(u64),
+    /// There are no ids left for the given number of new tasks
+    TaskIdsExhausted(usize),
+    InvalidThreadId { thread_id: u64, n_threads: u64 },
+    NoChildren(u64),
+    OutstandingTasks { phase: u64, task_ids: Vec<u64> },
+    ExportFailed(String),
+    ImportFailed(String),
+    ExecutionFailed(String),
+    FunctionIdNotFound(u64),
     AlreadyInitialized(&'static str),
+    NotPreInitialized,
+    NotInitialized,
+    Finished,
     InvalidFunctionName(String),
     SameTaskDependency,
+    InvalidCommand(ParseError),
+    IllegalTransition(TransitionError),
+    InvalidDependency(DependencyError),
+    HasDependents(RemovalError),
+    ReadFailed(String),
+    /// Not an error as such, but ends the session wherever input is read
+    Shutdown(Shutdown),
 }
 
-impl Display for UserInputError {
-    
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl UserInputError {
+    fn message(&self) -> String {
         type E = UserInputError;
         
-        let msg = match self {
+        match self {
             E::AlreadyInitialized(init) => format!("{} should only be called once. Will not emit event.", init),
+            E::NotPreInitialized => "Init can only be sent after PreInit.".to_string(),
+            E::NotInitialized => "Events can only be sent after Init.".to_string(),
+            E::Finished => "Finish was already sent, no more events are allowed.".to_string(),
             E::TaskIdNotFound(id) => format!("Task with id: {} not found.", id),
+            E::DuplicateTaskId(id) => format!("Task with id: {} already exists.", id),
+            E::TaskIdsExhausted(1) => "There is no task id left for a new task.".to_string(),
+            E::TaskIdsExhausted(tasks) => format!("There are not enough task ids left for {} tasks.", tasks),
+            E::NoChildren(id) => format!("Task with id: {} has no children.", id),
+            E::OutstandingTasks { phase, task_ids } => format!("Tasks {} have not finished, phase {} cannot end yet.", join_ids(task_ids), phase),
+            E::ExportFailed(e) => format!("Unable to export the task graph to {}", e),
+            E::ImportFailed(e) => format!("Unable to import the matrix from {}", e),
+            E::ExecutionFailed(e) => format!("Unable to start the worker threads: {}", e),
+            E::InvalidThreadId { thread_id, n_threads } => format!("Thread id: {} is out of range, the runtime has {} threads.", thread_id, n_threads),
+            E::FunctionIdNotFound(id) => format!("Function with id: {} not found.", id),
             E::InvalidFunctionName(name) => format!("Invalid Name: {}. Can only contain ASCII characters", name.trim()),
             E::SameTaskDependency => "Parent and Child cannot be the same Task.".to_string(),
-        };
-        write!(f, "Error while reading input:\n\t{}", msg)
+            E::InvalidCommand(e) => e.to_string(),
+            E::IllegalTransition(e) => e.to_string(),
+            E::InvalidDependency(e) => e.to_string(),
+            E::HasDependents(e) => e.to_string(),
+            E::ReadFailed(e) => format!("Unable to read input: {}", e),
+            E::Shutdown(Shutdown::Quit) => "Quitting.".to_string(),
+            E::Shutdown(Shutdown::EndOfInput) => "End of input reached.".to_string(),
+            E::Shutdown(Shutdown::Interrupted) => "Interrupted, shutting down.".to_string(),
+        }
+    }
+
+    /// The protocol rule the input would break, if it is a valid event at all
+    fn rule(&self) -> Option<&'static str> {
+        type E = UserInputError;
+
+        match self {
+            E::AlreadyInitialized(_) => Some("pre_init and init are sent once"),
+            E::NotPreInitialized => Some("init follows pre_init"),
+            E::NotInitialized => Some("events follow init"),
+            E::Finished => Some("no events after finish"),
+            E::TaskIdNotFound(_) => Some("events refer to existing tasks"),
+            E::DuplicateTaskId(_) => Some("task ids are unique"),
+            E::InvalidThreadId { .. } => Some("thread ids are below the number of threads"),
+            E::FunctionIdNotFound(_) => Some("labels are registered before they are used"),
+            E::SameTaskDependency => Some("tasks do not depend on themselves"),
+            E::IllegalTransition(_) => Some("tasks follow their lifecycle"),
+            E::InvalidDependency(DependencyError::Cycle(_)) => Some("the task graph has no cycles"),
+            E::InvalidDependency(DependencyError::Duplicate { .. }) => Some("dependencies are added once"),
+            E::HasDependents(_) => Some("tasks are removed after their dependents"),
+            E::OutstandingTasks { .. } => Some("tasks finish before the next barrier"),
+            E::NoChildren(_) | E::TaskIdsExhausted(_) | E::ExportFailed(_) | E::ImportFailed(_) | E::ExecutionFailed(_) | E::InvalidDependency(_) | E::InvalidFunctionName(_) | E::InvalidCommand(_)
+            | E::ReadFailed(_) | E::Shutdown(_) => None,
+        }
+    }
+}
+
+impl Display for UserInputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Error while reading input:\n\t{}", self.message())
     }
 }
 
 impl std::error::Error for UserInputError { }
 
-#[link(name = "ayudame", kind = "dylib")]
-extern {
-    fn ayu_event_preinit(rt: u64);
-    fn ayu_event_init(n_threads: u64);
-    fn ayu_event_addtask(task_id: u64, func_id: u64, priority: u64, scope_id: u64);
-    fn ayu_event_registerfunction(func_id: u64, name: *mut std::os::raw::c_char);
-    fn ayu_event_adddependency(to_id: u64, from_id: u64, memaddr: u64, orig_memaddr: u64);
-    fn ayu_event_addtasktoqueue(task_id: u64, thread_id: u64);
-    fn ayu_event_preruntask(task_id: u64, thread_id: u64);
-    fn ayu_event_runtask(task_id: u64);
-    fn ayu_event_postruntask(task_id: u64);
-    fn ayu_event_removetask(task_id: u64);
-    fn ayu_event_barrier();
-    fn ayu_event_waiton(task_id: u64);
-    fn ayu_event_finish();
+impl From<TransitionError> for UserInputError {
+    fn from(e: TransitionError) -> Self {
+        match e {
+            TransitionError::TaskNotFound(id) => UserInputError::TaskIdNotFound(id),
+            e => UserInputError::IllegalTransition(e),
+        }
+    }
+}
+
+impl From<DependencyError> for UserInputError {
+    fn from(e: DependencyError) -> Self {
+        match e {
+            DependencyError::TaskNotFound(id) => UserInputError::TaskIdNotFound(id),
+            e => UserInputError::InvalidDependency(e),
+        }
+    }
+}
+
+impl From<RemovalError> for UserInputError {
+    fn from(e: RemovalError) -> Self {
+        match e {
+            RemovalError::TaskNotFound(id) => UserInputError::TaskIdNotFound(id),
+            RemovalError::IllegalTransition(e) => UserInputError::IllegalTransition(e),
+            e => UserInputError::HasDependents(e),
+        }
+    }
 }
 
 fn main() {
-    // create event loop
+    let options = parse_options();
+
+    if options.help {
+        println!("{}\n\n{}", USAGE, COMMAND_SYNTAX);
+        return;
+    }
+
+    let mut sink: Box<dyn EventSink> = if options.dry_run {
+        Box::new(PrintSink::stdout())
+    } else {
+        load_libayudame(options.ayudame_lib)
+    };
+
+    if let Some(path) = &options.record {
+        let log = match OpenOptions::new().create(true).append(true).open(path) {
+            Ok(log) => log,
+            Err(e) => {
+                eprintln!("Unable to open event log {}: {}", path.display(), e);
+                std::process::exit(1);
+            },
+        };
+        sink = Box::new(RecordingSink::new(sink, log));
+    }
+
+    let sink: &mut dyn EventSink = &mut sink;
+
+    if let Some(path) = &options.replay {
+        let events = File::open(path)
+            .map_err(|e| LogError::Read(e.to_string()))
+            .and_then(|log| read_log(BufReader::new(log)));
+
+        match events {
+            Ok(events) => replay(&events, sink, &options.pacing),
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                std::process::exit(1);
+            },
+        }
+        return;
+    }
+
     let mut state = AppState::default();
+    state.chaos = options.chaos;
+    state.allow_cycles = options.allow_cycles;
+    state.removal_policy = options.removal_policy;
+    state.duplicate_policy = options.duplicate_policy;
+    state.barrier_policy = options.barrier_policy;
+    state.scheduling_policy = options.scheduling_policy;
+
+    state.runtime_id = options.runtime_id.unwrap_or(state.runtime_id);
+    state.n_threads = options.n_threads.unwrap_or(state.n_threads);
+
+    if let Startup::Delayed(delay) = options.startup {
+        thread::sleep(delay);
+    }
+    if options.startup != Startup::Manual {
+        let _ = create_pre_init(&mut state, sink, None);
+        let _ = create_init(&mut state, sink, None);
+    }
+
+    let errors = match options.script {
+        Some(path) => {
+            let (script, name): (Box<dyn BufRead + Send>, String) = if path.as_os_str() == "-" {
+                (Box::new(BufReader::new(io::stdin())), "<stdin>".to_string())
+            } else {
+                match File::open(&path) {
+                    Ok(file) => (Box::new(BufReader::new(file)), path.display().to_string()),
+                    Err(e) => {
+                        eprintln!("Unable to open script {}: {}", path.display(), e);
+                        std::process::exit(1);
+                    },
+                }
+            };
+
+            // keep-alive events are only sent while commands are typed interactively
+            Input::start(script, None);
+            run_script(&name, &mut state, sink, options.abort_on_error)
+        },
+        None => {
+            Input::start(BufReader::new(io::stdin()), options.keep_alive);
+            run(&mut state, sink)
+        },
+    };
+
+    if shut_down(&mut state, sink, options.drain) + errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+/// Parses the command line, preceded by the options in the config file if one is given
+fn parse_options() -> Options {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let parsed = Options::parse(args.clone()).and_then(|options| match &options.config {
+        Some(path) => match fs::read_to_string(path) {
+            Ok(config) => Options::parse(config_arguments(&config).into_iter().chain(args)),
+            Err(e) => {
+                eprintln!("Unable to read config file {}: {}", path.display(), e);
+                std::process::exit(1);
+            },
+        },
+        None => Ok(options),
+    });
+
+    match parsed {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        },
+    }
+}
+
+#[cfg(feature = "libayudame")]
+fn load_libayudame(path: Option<PathBuf>) -> Box<dyn EventSink> {
+    match LibAyudameSink::locate(path) {
+        Ok(sink) => Box::new(sink),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        },
+    }
+}
+
+#[cfg(not(feature = "libayudame"))]
+fn load_libayudame(_path: Option<PathBuf>) -> Box<dyn EventSink> {
+    eprintln!("Built without the libayudame feature, running in dry-run mode.");
+    Box::new(PrintSink::stdout())
+}
+
+/// Runs the interactive event loop, sending all events to `sink`, until the session ends.
+/// Returns the number of commands that failed.
+fn run(state: &mut AppState, sink: &mut dyn EventSink) -> usize {
+    let mut errors = 0;
 
-    let _ = create_pre_init(&mut state);
-    let _ = create_init(&mut state);
-    
     loop {
-        match ask_for_command() {
-            Command::AddTask => {
-                print_event_types();
+        match handle_user_input(state, sink) {
+            Ok(()) => (),
+            Err(UserInputError::Shutdown(_)) => return errors,
+            Err(e) => {
+                eprintln!("{}", e);
+                errors += 1;
+            },
+        }
+    }
+}
 
-                if let Err(e) = handle_user_input(&mut state) {
-                    eprintln!("{}", e);
+/// Executes every command of the script that is read as input, reporting failed commands with their line number.
+/// Returns the number of commands that failed.
+fn run_script(name: &str, state: &mut AppState, sink: &mut dyn EventSink, abort_on_error: bool) -> usize {
+    let mut errors = 0;
+
+    for line_number in 1.. {
+        let result = get_input()
+            .and_then(|line| Command::parse_line(&line).map_err(UserInputError::InvalidCommand))
+            .and_then(|command| match command {
+                Some(command) => execute(state, sink, command),
+                None => Ok(()),
+            });
+
+        match result {
+            Ok(()) => (),
+            Err(UserInputError::Shutdown(_)) => break,
+            Err(e) => {
+                eprintln!("{}:{}: {}", name, line_number, e);
+                errors += 1;
+
+                if abort_on_error {
+                    eprintln!("Aborting script.");
+                    break;
                 }
             },
-            Command::PrintState => println!("{}", state),
         }
     }
+
+    errors
 }
 
-fn ask_for_command() -> Command {
-    println!("Options:\n\t(a)dd new event\n\t(p)rint current state");
+/// Asks for the next command, calling `on_idle` whenever none was entered for the keep-alive interval
+fn ask_for_command(on_idle: &mut dyn FnMut()) -> Result<MenuOption> {
+    println!("Options:\n\t(a)dd new event\n\t(p)rint current state\n\t(h)elp on commands\n\t(q)uit\n\tor enter a command directly");
     loop {
-        break match get_input().trim() {
-            "a" => Command::AddTask,
-            "p" => Command::PrintState,
-            invalid => {
-                eprintln!("Invalid Option: {}, try again", invalid);
-                continue;
-            },
-        }   
+        let line = INPUT.with_borrow(|input| match input {
+            Some(input) => input.read_line(Some(on_idle)),
+            None => Err(UserInputError::Shutdown(Shutdown::EndOfInput)),
+        })?;
+
+        break Ok(match line.trim() {
+            "" => continue,
+            "a" => MenuOption::AddTask,
+            "p" => MenuOption::PrintState,
+            "h" => MenuOption::Help,
+            "q" => MenuOption::Command("quit".to_string()),
+            line => MenuOption::Command(line.to_string()),
+        })
     }
 }
 
@@ -117,84 +389,402 @@ fn print_event_types() {
     println!("{options_str}");
 }
 
-pub fn get_event_type() -> InputTypes { 
+fn get_event_type() -> Result<InputTypes> { 
     println!("Enter index of Event to send: ");
     
     loop {
-        let n = get_numerical_input();
+        let n = get_numerical_input()?;
 
-        break match_or_continue!(InputTypes::try_from(n), "Got Invalid Index, try again");
+        break Ok(match_or_continue!(InputTypes::try_from(n), "Got Invalid Index, try again"));
     } 
 }
 
-pub fn get_numerical_input() -> u64 {
+fn get_numerical_input() -> Result<u64> {
     loop {
-        let input = get_input();
-        break match_or_continue!(input.trim().parse::<u64>(), "Got non numeric input, try again");
+        let input = get_input()?;
+        break Ok(match_or_continue!(input.trim().parse::<u64>(), "Got non numeric input, try again"));
     }
 }
 
-pub fn get_input() -> String {
-    let mut input = String::new();
-    if io::stdin().read_line(&mut input).is_err() {
-        eprintln!("Unable to read user input, aborting...");
-        std::process::exit(1);
+/// Reads a number from `min` to `max`, or nothing to keep the default
+fn get_optional_numerical_input(min: u64, max: u64) -> Result<Option<u64>> {
+    loop {
+        break Ok(match get_input()?.trim() {
+            "" => None,
+            n => match n.parse::<u64>() {
+                Ok(n) if (min..=max).contains(&n) => Some(n),
+                _ if max == u64::MAX => {
+                    eprintln!("Invalid input, must be a number of at least {}", min);
+                    continue;
+                },
+                _ => {
+                    eprintln!("Invalid input, must be a number from {} to {}", min, max);
+                    continue;
+                },
+            },
+        });
     }
+}
 
-    input
+/// Reads the next line of input, failing with `UserInputError::Shutdown` once the session ends
+fn get_input() -> Result<String> {
+    INPUT.with_borrow(|input| match input {
+        Some(input) => input.read_line(None),
+        None => Err(UserInputError::Shutdown(Shutdown::EndOfInput)),
+    })
 }
 
-fn handle_user_input(state: &mut AppState) -> Result<()> {
-    match get_event_type() {
-        InputTypes::PreInit => create_pre_init(state),
-        InputTypes::Init => create_init(state),
-        InputTypes::AddTask => create_add_task(state),
-        InputTypes::RegisterFunction => create_register_function(state),
-        InputTypes::AddDependency => create_add_dependency(state),
-        InputTypes::AddTaskToQueue => create_add_task_to_queue(state),
-        InputTypes::PreRunTask => create_pre_run_task(state),
-        InputTypes::RunTask => create_run_task(state),
-        InputTypes::PostRunTask => create_post_run_task(state),
-        InputTypes::RemoveTask => create_remove_task(state),
-        InputTypes::Barrier => create_barrier(state),
-        InputTypes::WaitOn => create_wait_on(state),
-        InputTypes::Finish => create_finish(state),
+thread_local! {
+    static INPUT: RefCell<Option<Input>> = const { RefCell::new(None) };
+}
+
+/// What the input thread passes on
+enum InputLine {
+    Line(String),
+    Failed(io::Error),
+    End,
+    Interrupted,
+}
+
+/// Commands typed by the user or read from a script, read line by line by a separate thread,
+/// so that signals and keep-alive events can interrupt waiting for them
+struct Input {
+    lines: Receiver<InputLine>,
+    /// Time without input after which a keep-alive event is sent, while waiting for a command
+    keep_alive: Option<Duration>,
+}
+
+impl Input {
+    /// Starts reading lines from `reader`. SIGINT and SIGTERM end the input early.
+    fn start(reader: impl BufRead + Send + 'static, keep_alive: Option<Duration>) {
+        let (sender, lines) = mpsc::channel();
+
+        let interrupts = sender.clone();
+        if let Err(e) = ctrlc::set_handler(move || { let _ = interrupts.send(InputLine::Interrupted); }) {
+            eprintln!("Unable to handle SIGINT and SIGTERM: {}", e);
+        }
+
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let (line, failed) = match line {
+                    Ok(line) => (InputLine::Line(line), false),
+                    Err(e) => (InputLine::Failed(e), true),
+                };
+                if sender.send(line).is_err() || failed {
+                    break;
+                }
+            }
+            let _ = sender.send(InputLine::End);
+        });
+
+        INPUT.set(Some(Input { lines, keep_alive }));
+    }
+
+    /// Waits for the next line, calling `on_idle` whenever no line arrived for the keep-alive interval
+    fn read_line(&self, mut on_idle: Option<&mut dyn FnMut()>) -> Result<String> {
+        let line = loop {
+            let (Some(interval), Some(on_idle)) = (self.keep_alive, on_idle.as_mut()) else {
+                break self.lines.recv().unwrap_or(InputLine::End);
+            };
+
+            match self.lines.recv_timeout(interval) {
+                Ok(line) => break line,
+                Err(RecvTimeoutError::Timeout) => on_idle(),
+                Err(RecvTimeoutError::Disconnected) => break InputLine::End,
+            }
+        };
+
+        match line {
+            InputLine::Line(line) => Ok(line),
+            InputLine::Failed(e) => Err(UserInputError::ReadFailed(e.to_string())),
+            InputLine::End => Err(UserInputError::Shutdown(Shutdown::EndOfInput)),
+            InputLine::Interrupted => {
+                eprintln!("{}", UserInputError::Shutdown(Shutdown::Interrupted).message());
+                Err(UserInputError::Shutdown(Shutdown::Interrupted))
+            },
+        }
     }
 }
 
-fn create_pre_init(state: &mut AppState) -> Result<()> {
-    if state.is_pre_init {
-        return Err(UserInputError::AlreadyInitialized("PreInit"));
+fn handle_user_input(state: &mut AppState, sink: &mut dyn EventSink) -> Result<()> {
+    // the keep-alive sends real barriers, which end the current phase, from init until finish
+    let keep_alive = state.is_init && !state.is_finished;
+    let menu_option = ask_for_command(&mut || if keep_alive {
+        if let Err(e) = create_barrier(state, sink) {
+            eprintln!("{}", e);
+        }
+    })?;
+
+    let command = match menu_option {
+        MenuOption::AddTask => {
+            print_event_types();
+            ask_for_event(state, get_event_type()?)?
+        },
+        MenuOption::PrintState => Command::PrintState,
+        MenuOption::Help => {
+            println!("{}", COMMAND_SYNTAX);
+            return Ok(());
+        },
+        // fall back to asking for each argument, if none were given
+        MenuOption::Command(line) => match missing_arguments(&line) {
+            Some(event_type) => ask_for_event(state, event_type)?,
+            None => line.parse().map_err(UserInputError::InvalidCommand)?,
+        },
+    };
+
+    execute(state, sink, command)
+}
+
+/// Asks for all arguments needed to send an event of `event_type`
+fn ask_for_event(state: &AppState, event_type: InputTypes) -> Result<Command> {
+    let command = match event_type {
+        InputTypes::PreInit => {
+            println!("Enter runtime id: (default is {})", state.runtime_id);
+            Command::PreInit { runtime_id: get_optional_numerical_input(0, u64::MAX)? }
+        },
+        InputTypes::Init => {
+            println!("Enter number of threads: (default is {})", state.n_threads);
+            Command::Init { n_threads: get_optional_numerical_input(1, MAX_THREADS)? }
+        },
+        InputTypes::AddTask => ask_for_task(state)?,
+        InputTypes::RegisterFunction => ask_for_function()?,
+        InputTypes::AddDependency => ask_for_dependency(state)?,
+        InputTypes::AddTaskToQueue => Command::AddTaskToQueue { task_id: ask_for_task_id(state)? },
+        InputTypes::PreRunTask => Command::PreRunTask { task_id: ask_for_task_id(state)? },
+        InputTypes::RunTask => Command::RunTask { task_id: specify_task_id(state)? },
+        InputTypes::PostRunTask => Command::PostRunTask { task_id: specify_task_id(state)? },
+        InputTypes::RemoveTask => Command::RemoveTask { task_id: specify_task_id(state)?, policy: None },
+        InputTypes::Barrier => Command::Barrier,
+        InputTypes::WaitOn => ask_for_wait_on(state)?,
+        InputTypes::Finish => Command::Finish,
+    };
+
+    Ok(command)
+}
+
+/// Sends the event corresponding to `command` and updates the state accordingly.
+/// Events for tasks depending on a task that is waited on are held back until that task has finished.
+fn execute(state: &mut AppState, sink: &mut dyn EventSink, command: Command) -> Result<()> {
+    if let Some(task_id) = command.task_id() {
+        if let Some(waited_on) = state.blocking_wait(task_id) {
+            println!("Task {} depends on task {}, which is waited on. Holding back its event until task {} has finished.", task_id, waited_on, waited_on);
+            state.hold(command);
+            return Ok(());
+        }
+    }
+
+    let result = execute_or_force(state, sink, command);
+    if result.is_ok() {
+        for command in state.release_held() {
+            if let Err(e) = execute(state, sink, command) {
+                eprintln!("{}", e);
+            }
+        }
     }
-    unsafe { 
-        ayu_event_preinit(0); 
+
+    result
+}
+
+/// In chaos mode, events breaking the protocol are sent anyway.
+fn execute_or_force(state: &mut AppState, sink: &mut dyn EventSink, command: Command) -> Result<()> {
+    let result = apply(state, sink, command.clone());
+    if !state.chaos {
+        return result;
     }
 
-    state.is_pre_init = true;
-    Ok(())
+    match result {
+        Err(e) => match e.rule() {
+            Some(rule) => {
+                eprintln!("Chaos: sending anyway, breaking rule \"{}\": {}", rule, e.message());
+                // the event itself may be valid, so send it as it would have been sent before finish or after init
+                match e {
+                    UserInputError::Finished => {
+                        state.is_finished = false;
+                        let result = execute_or_force(state, sink, command);
+                        state.is_finished = true;
+                        result
+                    },
+                    UserInputError::NotInitialized => {
+                        state.is_init = true;
+                        let result = execute_or_force(state, sink, command);
+                        state.is_init = false;
+                        result
+                    },
+                    _ => {
+                        force(state, sink, command);
+                        Ok(())
+                    },
+                }
+            },
+            None => Err(e),
+        },
+        ok => ok,
+    }
 }
 
-fn create_init(state: &mut AppState) -> Result<()> {
-    if state.is_init {
-        return Err(UserInputError::AlreadyInitialized("Init"));
+fn apply(state: &mut AppState, sink: &mut dyn EventSink, command: Command) -> Result<()> {
+    if state.is_finished && !matches!(command, Command::PrintState | Command::Export { .. } | Command::Quit) {
+        return Err(UserInputError::Finished);
     }
-    unsafe {
-        ayu_event_init(2);
+    if !state.is_init && !matches!(command, Command::PreInit { .. } | Command::Init { .. } | Command::Finish | Command::RemoveDependency { .. }
+        | Command::PrintState | Command::Export { .. } | Command::Quit) {
+        return Err(UserInputError::NotInitialized);
     }
 
-    state.is_init = true;
+    match command {
+        Command::PreInit { runtime_id } => create_pre_init(state, sink, runtime_id),
+        Command::Init { n_threads } => create_init(state, sink, n_threads),
+        Command::AddTask { task_id, is_critical, thread_id, function_id } => create_add_task(state, sink, task_id, is_critical, thread_id, function_id),
+        Command::RegisterFunction { name } => create_register_function(state, sink, name),
+        Command::AddDependency { parent_id, child_id } => create_add_dependency(state, sink, parent_id, child_id),
+        Command::RemoveDependency { parent_id, child_id } => state.remove_dependency(parent_id, child_id).map_err(UserInputError::InvalidDependency),
+        Command::AddTaskToQueue { task_id } => create_add_task_to_queue(state, sink, task_id),
+        Command::PreRunTask { task_id } => create_pre_run_task(state, sink, task_id),
+        Command::RunTask { task_id } => create_run_task(state, sink, task_id),
+        Command::PostRunTask { task_id } => create_post_run_task(state, sink, task_id),
+        Command::RemoveTask { task_id, policy } => create_remove_task(state, sink, task_id, policy.unwrap_or(state.removal_policy)),
+        Command::Barrier => create_barrier(state, sink),
+        Command::WaitOn { target } => create_wait_on(state, sink, target),
+        Command::Finish => create_finish(state, sink),
+        Command::PrintState => {
+            println!("{}", state);
+            Ok(())
+        },
+        Command::Export { path } => create_export(state, &path),
+        Command::Generate { generator } => create_generate(state, sink, &generator),
+        Command::Import { path, order } => create_import(state, sink, &path, order),
+        Command::Schedule { policy } => create_schedule(state, sink, policy.unwrap_or(state.scheduling_policy)),
+        Command::Simulate { durations, policy } => create_simulate(state, sink, &durations, policy.unwrap_or(state.scheduling_policy)),
+        Command::Execute { execution, policy } => create_execute(state, sink, &execution, policy.unwrap_or(state.scheduling_policy)),
+        Command::Quit => Err(UserInputError::Shutdown(Shutdown::Quit)),
+    }
+}
 
-    Ok(())
+/// Sends the event for `command` without any checks,
+/// updating the state only as far as it is still consistent afterwards
+fn force(state: &mut AppState, sink: &mut dyn EventSink, command: Command) {
+    let thread_of = |state: &AppState, task_id| state.get_task(task_id).map_or(0, |t| t.into_raw_parts().3);
+
+    match command {
+        Command::PreInit { runtime_id } => {
+            state.runtime_id = runtime_id.unwrap_or(state.runtime_id);
+            sink.pre_init(state.runtime_id);
+            state.is_pre_init = true;
+        },
+        Command::Init { n_threads } => {
+            state.n_threads = n_threads.unwrap_or(state.n_threads);
+            sink.init(state.n_threads);
+            state.is_init = true;
+        },
+        Command::AddTask { task_id, is_critical, thread_id, function_id } => {
+            let task_id = task_id.unwrap_or_else(|| state.next_task_id());
+            // fails for duplicate ids, in which case the existing task is kept, and invalid thread ids.
+            // Labels that were never registered are sent, but the task is kept without one
+            if state.create_task_with_id(task_id, is_critical, function_id, thread_id).is_err() && function_id.is_some() {
+                let _ = state.create_task_with_id(task_id, is_critical, None, thread_id);
+            }
+            sink.add_task(task_id, function_id.unwrap_or(task_id), is_critical as u64, thread_id);
+        },
+        Command::AddDependency { parent_id, child_id } => {
+            let (memaddr, orig_memaddr) = memory_addresses(parent_id, child_id);
+            sink.add_dependency(parent_id, child_id, memaddr, orig_memaddr);
+        },
+        Command::AddTaskToQueue { task_id } => {
+            sink.add_task_to_queue(task_id, thread_of(state, task_id));
+            state.force_task_state(task_id, TaskState::Queued);
+        },
+        Command::PreRunTask { task_id } => {
+            sink.pre_run_task(task_id, thread_of(state, task_id));
+            state.force_task_state(task_id, TaskState::PreRun);
+        },
+        Command::RunTask { task_id } => {
+            sink.run_task(task_id);
+            state.force_task_state(task_id, TaskState::Running);
+        },
+        Command::PostRunTask { task_id } => {
+            sink.post_run_task(task_id);
+            state.force_task_state(task_id, TaskState::PostRun);
+        },
+        Command::RemoveTask { task_id, .. } => {
+            sink.remove_task(task_id);
+            state.force_task_state(task_id, TaskState::Removed);
+        },
+        Command::Barrier => {
+            sink.barrier();
+            state.barrier();
+        },
+        Command::WaitOn { target: WaitTarget::Task(task_id) } => sink.wait_on(task_id),
+        Command::WaitOn { target: WaitTarget::ChildrenOf(task_id) } => {
+            for child in state.get_children(task_id).unwrap_or_default() {
+                sink.wait_on(child);
+            }
+        },
+        Command::Finish => sink.finish(),
+        // these never break a protocol rule
+        Command::RegisterFunction { .. } | Command::RemoveDependency { .. } | Command::PrintState | Command::Export { .. }
+        | Command::Generate { .. } | Command::Import { .. } | Command::Schedule { .. } | Command::Simulate { .. } | Command::Execute { .. } | Command::Quit => (),
+    }
+}
+
+/// Ends the session: drains all tasks if `drain` is set and sends finish, unless it was sent before.
+/// Events that are still held back are dropped. Returns the number of events that could not be sent.
+fn shut_down(state: &mut AppState, sink: &mut dyn EventSink, drain: bool) -> usize {
+    let held = state.discard_held();
+    if !held.is_empty() {
+        println!("Dropping {} held back events.", held.len());
+    }
+
+    if !state.is_pre_init || state.is_finished {
+        return 0;
+    }
+
+    let errors = if drain && state.is_init { drain_tasks(state, sink) } else { 0 };
+    let _ = create_finish(state, sink);
+
+    errors
 }
 
-fn create_add_task(state: &mut AppState) -> Result<()>{
+/// Runs every unfinished task to completion and removes all tasks, in the order they were created.
+/// Returns the number of events that could not be sent.
+fn drain_tasks(state: &mut AppState, sink: &mut dyn EventSink) -> usize {
+    let mut errors = 0;
+
+    for task_id in state.task_ids() {
+        let remaining = match state.get_task(task_id).map(|t| t.get_state()) {
+            Some(TaskState::Queued) => [TaskState::PreRun, TaskState::Running, TaskState::PostRun].as_slice(),
+            Some(TaskState::PreRun) => &[TaskState::Running, TaskState::PostRun],
+            Some(TaskState::Running) => &[TaskState::PostRun],
+            Some(TaskState::Added | TaskState::PostRun) => &[],
+            Some(TaskState::Removed) | None => continue,
+        };
+
+        let commands = remaining.iter()
+            .map(|next| match next {
+                TaskState::PreRun => Command::PreRunTask { task_id },
+                TaskState::Running => Command::RunTask { task_id },
+                _ => Command::PostRunTask { task_id },
+            })
+            .chain([Command::RemoveTask { task_id, policy: Some(RemovalPolicy::Detach) }]);
+
+        // events of tasks depending on a waited on task are held back until it has been drained as well
+        for command in commands {
+            if let Err(e) = execute(state, sink, command) {
+                eprintln!("{}", e);
+                errors += 1;
+                break;
+            }
+        }
+    }
 
-    // TODO: Return with error on wrong input
+    errors + state.discard_held().len()
+}
+
+fn ask_for_task(state: &AppState) -> Result<Command> {
     println!("Specify Task to add: (leave empty for default values");
 
     println!("Is task critical (default is false)? (y/n)");
     let is_critical = loop {
-        match get_input().trim() {
+        match get_input()?.trim() {
             "y" => break true,
             "n" => break false,
             "" => break false,
@@ -204,7 +794,7 @@ fn create_add_task(state: &mut AppState) -> Result<()>{
 
     println!("Enter thread id: (default is 0)");
     let thread_id = loop {
-        break match get_input().trim() {
+        break match get_input()?.trim() {
             "" => 0,
             n => match_or_continue!(n.parse::<u64>(), PARSE_UNSIGNED_ERROR_MSG),
         };
@@ -212,149 +802,329 @@ fn create_add_task(state: &mut AppState) -> Result<()>{
 
     println!("Choose a label for task: ");
     state.list_functions();
-    let task = loop {
-        let function_id = match get_input().trim() {
+    let function_id = loop {
+        break match get_input()?.trim() {
             "" => None,
-            input => Some(match_or_continue!(input.parse::<u64>(), PARSE_UNSIGNED_ERROR_MSG)),
+            input => {
+                let id = match_or_continue!(input.parse::<u64>(), PARSE_UNSIGNED_ERROR_MSG);
+                match_or_continue!(state.get_function(id).ok_or(()), "Function with provided id not found");
+                Some(id)
+            },
         };
-        break match_or_continue!(state.create_task(is_critical, function_id, thread_id), "Function with provided id not found");
     };
 
-    let (task_id, func_id, priority, scope_id) = task.into_raw_parts();
+    Ok(Command::AddTask { task_id: None, is_critical, thread_id, function_id })
+}
+
+fn ask_for_function() -> Result<Command> {
+    println!("Enter a name for function (empty for default)");
+    Ok(Command::RegisterFunction { name: get_input()? })
+}
+
+fn ask_for_dependency(state: &AppState) -> Result<Command> {
+    state.list_tasks();
+
+    println!("Enter parent, then child id");
 
-    unsafe {
-        ayu_event_addtask(task_id, func_id, priority, scope_id);
+    let parent_id = specify_task_id(state)?;
+    let child_id = specify_task_id(state)?;
+
+    Ok(Command::AddDependency { parent_id, child_id })
+}
+
+fn ask_for_wait_on(state: &AppState) -> Result<Command> {
+    println!("Wait on a (t)ask or on all (c)hildren of a task? (default is t)");
+    let children = loop {
+        match get_input()?.trim() {
+            "t" | "" => break false,
+            "c" => break true,
+            invalid => eprintln!("Invalid option: {}", invalid),
+        }
+    };
+
+    let task_id = specify_task_id(state)?;
+    let target = if children { WaitTarget::ChildrenOf(task_id) } else { WaitTarget::Task(task_id) };
+
+    Ok(Command::WaitOn { target })
+}
+
+fn ask_for_task_id(state: &AppState) -> Result<u64> {
+    state.list_tasks();
+    get_numerical_input()
+}
+
+fn create_pre_init(state: &mut AppState, sink: &mut dyn EventSink, runtime_id: Option<u64>) -> Result<()> {
+    if state.is_pre_init {
+        return Err(UserInputError::AlreadyInitialized("PreInit"));
     }
+    state.runtime_id = runtime_id.unwrap_or(state.runtime_id);
+    sink.pre_init(state.runtime_id);
 
+    state.is_pre_init = true;
     Ok(())
 }
 
-// 
-fn create_register_function(state: &mut AppState) -> Result<()> {
-    println!("Enter a name for function (empty for default)");
-    let name = get_input();
-    let function = state.create_function(name.clone()).ok_or(UserInputError::InvalidFunctionName(name))?;
+fn create_init(state: &mut AppState, sink: &mut dyn EventSink, n_threads: Option<u64>) -> Result<()> {
+    if state.is_init {
+        return Err(UserInputError::AlreadyInitialized("Init"));
+    }
+    if !state.is_pre_init {
+        return Err(UserInputError::NotPreInitialized);
+    }
+    state.n_threads = n_threads.unwrap_or(state.n_threads);
+    sink.init(state.n_threads);
 
-    let (id, name) = function.into_raw_parts();
+    state.is_init = true;
 
-    unsafe {
-        ayu_event_registerfunction(id, name);
-    }
+    Ok(())
+}
+
+fn create_add_task(state: &mut AppState, sink: &mut dyn EventSink, task_id: Option<u64>, is_critical: bool, thread_id: u64, function_id: Option<u64>) -> Result<()> {
+    let task_id = match task_id {
+        Some(id) if state.does_task_exist(id) => return Err(UserInputError::DuplicateTaskId(id)),
+        Some(id) => id,
+        None if state.remaining_task_ids() == 0 => return Err(UserInputError::TaskIdsExhausted(1)),
+        None => state.next_task_id(),
+    };
+    check_thread_id(state, thread_id)?;
+
+    let task = state.create_task_with_id(task_id, is_critical, function_id, thread_id)
+        .map_err(|_| UserInputError::FunctionIdNotFound(function_id.unwrap_or_default()))?;
+
+    let (task_id, func_id, priority, scope_id) = task.into_raw_parts();
+
+    sink.add_task(task_id, func_id, priority, scope_id);
 
     Ok(())
 }
 
-fn create_add_dependency(state: &mut AppState) -> Result<()> {
-    state.list_tasks();
+fn create_register_function(state: &mut AppState, sink: &mut dyn EventSink, name: String) -> Result<()> {
+    let function = state.create_function(name.clone()).ok_or(UserInputError::InvalidFunctionName(name))?;
 
-    println!("Enter parent, then child id");
+    sink.register_function(function.id, function.c_name());
 
-    let parent_id = specify_task_id(state)?;
-    let child_id = specify_task_id(state)?;
+    Ok(())
+}
 
+fn create_add_dependency(state: &mut AppState, sink: &mut dyn EventSink, parent_id: u64, child_id: u64) -> Result<()> {
     if child_id == parent_id {
         return Err(UserInputError::SameTaskDependency);
     } 
 
-    state.add_dependency(parent_id, child_id);
+    for id in [parent_id, child_id] {
+        if !state.does_task_exist(id) {
+            return Err(UserInputError::TaskIdNotFound(id));
+        }
+    }
 
-    unsafe {
-        ayu_event_adddependency(parent_id, child_id, 0xffffeeee | parent_id, 0xffffeee | child_id);
+    match state.add_dependency(parent_id, child_id) {
+        Err(DependencyError::Duplicate { .. }) if state.duplicate_policy == DuplicatePolicy::Ignore => return Ok(()),
+        Err(DependencyError::Duplicate { .. }) if state.duplicate_policy == DuplicatePolicy::Resend => (),
+        result => result?,
     }
+
+    let (memaddr, orig_memaddr) = memory_addresses(parent_id, child_id);
+    sink.add_dependency(parent_id, child_id, memaddr, orig_memaddr);
     Ok(())
 }
 
-fn create_add_task_to_queue(state: & AppState) -> Result<()> {
-    state.list_tasks();
-    
-    let task_id = get_numerical_input();
+fn create_add_task_to_queue(state: &AppState, sink: &mut dyn EventSink, task_id: u64) -> Result<()> {
     let (_, _, _, scope_id) = state.get_task(task_id).ok_or(UserInputError::TaskIdNotFound(task_id))?.into_raw_parts();
+    check_thread_id(state, scope_id)?;
+    state.transition_task(task_id, TaskState::Queued)?;
 
-    unsafe {
-        ayu_event_addtasktoqueue(task_id, scope_id);
-    }
+    sink.add_task_to_queue(task_id, scope_id);
 
     Ok(())
 }
 
-fn create_pre_run_task(state: &AppState) -> Result<()> {
-    state.list_tasks();
-    let task_id = get_numerical_input();
-
+fn create_pre_run_task(state: &AppState, sink: &mut dyn EventSink, task_id: u64) -> Result<()> {
     let (_, _, _, scope_id) = state.get_task(task_id).ok_or(UserInputError::TaskIdNotFound(task_id))?.into_raw_parts();
+    check_thread_id(state, scope_id)?;
+    state.transition_task(task_id, TaskState::PreRun)?;
 
-    unsafe {
-        ayu_event_preruntask(task_id, scope_id);
-    }
+    sink.pre_run_task(task_id, scope_id);
 
     Ok(())
 }
 
-fn create_run_task(state: &AppState) -> Result<()> {
-    state.list_tasks();
-    let task_id = specify_task_id(state)?;
+fn create_run_task(state: &AppState, sink: &mut dyn EventSink, task_id: u64) -> Result<()> {
+    state.transition_task(task_id, TaskState::Running)?;
 
-    unsafe {
-        ayu_event_runtask(task_id);
+    sink.run_task(task_id);
+
+    Ok(())
+}
+
+fn create_post_run_task(state: &AppState, sink: &mut dyn EventSink, task_id: u64) -> Result<()> {
+    state.transition_task(task_id, TaskState::PostRun)?;
+
+    sink.post_run_task(task_id);
+
+    Ok(())
+}
+
+fn create_remove_task(state: &mut AppState, sink: &mut dyn EventSink, task_id: u64, policy: RemovalPolicy) -> Result<()> {
+    for task_id in state.remove_task(task_id, policy)? {
+        sink.remove_task(task_id);
     }
 
     Ok(())
 }
 
-fn create_post_run_task(state: &AppState) -> Result<()> {
-    state.list_tasks();
-    let task_id = specify_task_id(state)?;
+fn create_barrier(state: &mut AppState, sink: &mut dyn EventSink) -> Result<()> {
+    let outstanding = state.outstanding_tasks();
+    if !outstanding.is_empty() {
+        let phase = state.current_phase();
+        match state.barrier_policy {
+            BarrierPolicy::Report => println!("Tasks outstanding at the end of phase {}: {}", phase, join_ids(&outstanding)),
+            BarrierPolicy::Warn => eprintln!("Warning: tasks {} have not finished at the end of phase {}.", join_ids(&outstanding), phase),
+            BarrierPolicy::Refuse => return Err(UserInputError::OutstandingTasks { phase, task_ids: outstanding }),
+        }
+    }
+
+    sink.barrier();
+    state.barrier();
 
-    unsafe {
-        ayu_event_postruntask(task_id);
+    Ok(())
+}
+
+fn create_export(state: &AppState, path: &Path) -> Result<()> {
+    fs::write(path, state.to_dot()).map_err(|e| UserInputError::ExportFailed(format!("{}: {}", path.display(), e)))
+}
+
+fn create_generate(state: &mut AppState, sink: &mut dyn EventSink, generator: &Generator) -> Result<()> {
+    let spec = generator.generate();
+    let task_ids = spec.build(state, sink).map_err(|e| UserInputError::TaskIdsExhausted(e.tasks))?;
+
+    match (task_ids.first(), task_ids.last()) {
+        (Some(first), Some(last)) => println!("Generated tasks {} to {} with {} dependencies.", first, last, spec.dependencies.len()),
+        _ => println!("Generated an empty graph."),
     }
 
     Ok(())
 }
 
-fn create_remove_task(state: &mut AppState) -> Result<()> {
-    state.list_tasks();
-    let task_id = specify_task_id(state)?;
+fn create_import(state: &mut AppState, sink: &mut dyn EventSink, path: &Path, order: EliminationOrder) -> Result<()> {
+    let matrix = File::open(path)
+        .map_err(|e| MatrixError::Read(e.to_string()))
+        .and_then(|file| read_matrix_market(BufReader::new(file)))
+        .map_err(|e| UserInputError::ImportFailed(format!("{}: {}", path.display(), e)))?;
+
+    let symbolic = SymbolicFactorization::analyze(&matrix, order);
+    let spec = symbolic.graph();
+    let task_ids = spec.build(state, sink).map_err(|e| UserInputError::TaskIdsExhausted(e.tasks))?;
+
+    println!(
+        "Factor of {} columns has {} nonzeros in {} supernodes.",
+        matrix.size, symbolic.nonzeros(), symbolic.supernodes.len(),
+    );
+    match (task_ids.first(), task_ids.last()) {
+        (Some(first), Some(last)) => println!("Generated tasks {} to {} with {} dependencies.", first, last, spec.dependencies.len()),
+        _ => println!("Generated an empty graph."),
+    }
 
-    state.delete_task(task_id).ok_or(UserInputError::TaskIdNotFound(task_id))?;
+    Ok(())
+}
 
-    unsafe {
-        ayu_event_removetask(task_id);
+fn create_schedule(state: &mut AppState, sink: &mut dyn EventSink, policy: SchedulingPolicy) -> Result<()> {
+    let picked = schedule(state, sink, policy.scheduler(state.n_threads).as_mut());
+    if picked.is_empty() {
+        println!("No task is ready.");
+    }
+
+    // only the threads that got tasks, there may be many more
+    let mut by_thread: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+    for &(task_id, thread_id) in &picked {
+        by_thread.entry(thread_id).or_default().push(task_id);
+    }
+    for (thread_id, task_ids) in by_thread {
+        println!("Thread {}: tasks {}", thread_id, join_ids(&task_ids));
     }
 
     Ok(())
 }
 
-fn create_barrier(_state: &AppState) -> Result<()> {
-    unsafe {
-        ayu_event_barrier();
+fn create_simulate(state: &mut AppState, sink: &mut dyn EventSink, durations: &Durations, policy: SchedulingPolicy) -> Result<()> {
+    // the simulation runs every task, whether it is waited on or not
+    let held = state.discard_held();
+    if !held.is_empty() {
+        println!("Dropping {} held back events.", held.len());
     }
 
+    let mut scheduler = policy.scheduler(state.n_threads);
+    println!("{}", simulate(state, sink, durations, scheduler.as_mut()));
+
     Ok(())
 }
 
-fn create_wait_on(_state: &AppState) -> Result<()> {
-    unsafe {
-        ayu_event_waiton(0);
+fn create_execute(state: &mut AppState, sink: &mut dyn EventSink, execution: &Execution, policy: SchedulingPolicy) -> Result<()> {
+    // like a simulation, the execution runs every task
+    let held = state.discard_held();
+    if !held.is_empty() {
+        println!("Dropping {} held back events.", held.len());
     }
 
+    let report = executor::execute(state, sink, execution, policy.scheduler(state.n_threads))
+        .map_err(|e| UserInputError::ExecutionFailed(e.to_string()))?;
+    println!("{}", report);
+
     Ok(())
 }
 
-fn create_finish(_state: &AppState) -> Result<()> {
-    unsafe {
-        ayu_event_finish();
+fn join_ids(ids: &[u64]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+fn create_wait_on(state: &AppState, sink: &mut dyn EventSink, target: WaitTarget) -> Result<()> {
+    let task_ids = match target {
+        WaitTarget::Task(task_id) => {
+            check_task_id(state, task_id)?;
+            vec![task_id]
+        },
+        WaitTarget::ChildrenOf(task_id) => {
+            let children = state.get_children(task_id).ok_or(UserInputError::TaskIdNotFound(task_id))?;
+            if children.is_empty() {
+                return Err(UserInputError::NoChildren(task_id));
+            }
+            children
+        },
+    };
+
+    for task_id in task_ids {
+        state.wait_on(task_id);
+        sink.wait_on(task_id);
     }
 
     Ok(())
 }
 
+fn create_finish(state: &mut AppState, sink: &mut dyn EventSink) -> Result<()> {
+    sink.finish();
+
+    state.is_finished = true;
+
+    Ok(())
+}
+
 fn specify_task_id(state: &AppState) -> Result<u64> {
     println!("Select Task: ");
-    let id = get_numerical_input() as u64;
+    let id = get_numerical_input()?;
+    check_task_id(state, id)?;
+    Ok(id)
+}
+
+fn check_thread_id(state: &AppState, thread_id: u64) -> Result<()> {
+    match state.is_valid_thread_id(thread_id) {
+        true => Ok(()),
+        false => Err(UserInputError::InvalidThreadId { thread_id, n_threads: state.n_threads }),
+    }
+}
+
+fn check_task_id(state: &AppState, id: u64) -> Result<()> {
     if !state.does_task_exist(id) {
         Err(UserInputError::TaskIdNotFound(id))
     } else {
-        Ok(id)
+        Ok(())
     }
-}
\ No newline at end of file
+}
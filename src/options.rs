@@ -0,0 +1,309 @@
+use std::{fmt::Display, path::PathBuf, time::Duration};
+
+use crate::{event_log::{Pacing, Timing}, BarrierPolicy, DuplicatePolicy, RemovalPolicy, SchedulingPolicy, MAX_THREADS};
+
+pub const USAGE: &str =
+"Usage: ayudame_wrapper [OPTIONS]
+
+Options:
+    --ayudame-lib <PATH>    Path to the ayudame library (default: $AYUDAME_LIB, then the library search path)
+    --config <PATH>         Read options from a file, one `<option> = <value>` per line, the command line takes precedence
+    --runtime-id <ID>       Runtime id sent with pre_init (default: 0)
+    --threads <N>           Number of threads sent with init, at most 4096, thread ids have to be below it (default: 2)
+    --startup <MODE>        When pre_init and init are sent: auto right away, manual by hand or delay=<MS> after MS milliseconds (default: auto)
+    --keep-alive <MS>       Send a barrier, ending the phase, whenever no command was entered for MS milliseconds after init
+    --dry-run               Print events to stdout instead of sending them to ayudame
+    --record <PATH>         Append every event that is sent to an event log
+    --replay <PATH>         Send all events of an event log written by --record, then exit
+    --replay-speed <FACTOR> Replay events this many times faster than recorded, or as fast as possible with max (default: 1)
+    --max-rate <N>          Replay at most N events per second
+    --chaos                 Send events even if they break the protocol, reporting which rule they break
+    --allow-cycles          Accept dependencies that close a cycle in the task graph
+    --on-remove <POLICY>    What happens to the dependents of removed tasks: refuse, cascade or detach (default: detach)
+    --on-duplicate <POLICY> What happens when a dependency is added again: error, ignore or resend (default: error)
+    --at-barrier <POLICY>   What happens at barriers before all tasks have finished: report, warn or refuse (default: report)
+    --scheduler <POLICY>    How ready tasks are assigned to threads: fifo, lifo, critical, affinity or steal (default: fifo)
+    --drain                 Before finishing, run unfinished tasks to completion and remove all tasks
+    --script <PATH>         Read commands from a file instead of asking for them, - reads them from stdin
+    --abort-on-error        Stop executing a script at the first command that fails
+    -h, --help              Print this message";
+
+/// When the `pre_init` and `init` events are sent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Startup {
+    /// Right after starting
+    #[default]
+    Automatic,
+    /// Only when entered as commands
+    Manual,
+    /// After waiting for the given time
+    Delayed(Duration),
+}
+
+/// Options that can be passed on the command line
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub config: Option<PathBuf>,
+    pub runtime_id: Option<u64>,
+    pub n_threads: Option<u64>,
+    pub startup: Startup,
+    /// Time without input after which a keep-alive event is sent
+    pub keep_alive: Option<Duration>,
+    pub ayudame_lib: Option<PathBuf>,
+    pub dry_run: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub pacing: Pacing,
+    pub chaos: bool,
+    pub allow_cycles: bool,
+    pub removal_policy: RemovalPolicy,
+    pub duplicate_policy: DuplicatePolicy,
+    pub barrier_policy: BarrierPolicy,
+    pub scheduling_policy: SchedulingPolicy,
+    /// Remove all tasks when the session ends
+    pub drain: bool,
+    pub script: Option<PathBuf>,
+    pub abort_on_error: bool,
+    pub help: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArgumentError {
+    UnknownArgument(String),
+    MissingValue(&'static str),
+    InvalidValue(&'static str, String),
+}
+
+impl Display for ArgumentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ArgumentError::UnknownArgument(arg) => write!(f, "Unknown argument: {}", arg),
+            ArgumentError::MissingValue(arg) => write!(f, "Missing value for argument: {}", arg),
+            ArgumentError::InvalidValue(arg, value) => write!(f, "Invalid value for argument {}: {}", arg, value),
+        }
+    }
+}
+
+impl std::error::Error for ArgumentError { }
+
+impl Options {
+    /// Parses the arguments, without the name of the binary
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ArgumentError> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => options.config = Some(args.next().ok_or(ArgumentError::MissingValue("--config"))?.into()),
+                "--runtime-id" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--runtime-id"))?;
+                    options.runtime_id = Some(value.parse().map_err(|_| ArgumentError::InvalidValue("--runtime-id", value))?);
+                },
+                "--threads" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--threads"))?;
+                    options.n_threads = match value.parse() {
+                        Ok(n) if (1..=MAX_THREADS).contains(&n) => Some(n),
+                        _ => return Err(ArgumentError::InvalidValue("--threads", value)),
+                    };
+                },
+                "--ayudame-lib" => options.ayudame_lib = Some(args.next().ok_or(ArgumentError::MissingValue("--ayudame-lib"))?.into()),
+                "--startup" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--startup"))?;
+                    options.startup = match value.as_str() {
+                        "auto" => Startup::Automatic,
+                        "manual" => Startup::Manual,
+                        _ => match value.strip_prefix("delay=").map(str::parse) {
+                            Some(Ok(ms)) => Startup::Delayed(Duration::from_millis(ms)),
+                            _ => return Err(ArgumentError::InvalidValue("--startup", value)),
+                        },
+                    };
+                },
+                "--keep-alive" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--keep-alive"))?;
+                    options.keep_alive = match value.parse() {
+                        Ok(ms) if ms > 0 => Some(Duration::from_millis(ms)),
+                        _ => return Err(ArgumentError::InvalidValue("--keep-alive", value)),
+                    };
+                },
+                "--dry-run" => options.dry_run = true,
+                "--record" => options.record = Some(args.next().ok_or(ArgumentError::MissingValue("--record"))?.into()),
+                "--replay" => options.replay = Some(args.next().ok_or(ArgumentError::MissingValue("--replay"))?.into()),
+                "--replay-speed" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--replay-speed"))?;
+                    options.pacing.timing = match value.as_str() {
+                        "max" => Timing::AsFastAsPossible,
+                        speed => Timing::Original { speed: parse_positive("--replay-speed", speed)? },
+                    };
+                },
+                "--max-rate" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--max-rate"))?;
+                    options.pacing.max_rate = Some(parse_positive("--max-rate", &value)?);
+                },
+                "--chaos" => options.chaos = true,
+                "--allow-cycles" => options.allow_cycles = true,
+                "--on-remove" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--on-remove"))?;
+                    options.removal_policy = value.parse().map_err(|_| ArgumentError::InvalidValue("--on-remove", value))?;
+                },
+                "--on-duplicate" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--on-duplicate"))?;
+                    options.duplicate_policy = value.parse().map_err(|_| ArgumentError::InvalidValue("--on-duplicate", value))?;
+                },
+                "--at-barrier" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--at-barrier"))?;
+                    options.barrier_policy = value.parse().map_err(|_| ArgumentError::InvalidValue("--at-barrier", value))?;
+                },
+                "--scheduler" => {
+                    let value = args.next().ok_or(ArgumentError::MissingValue("--scheduler"))?;
+                    options.scheduling_policy = value.parse().map_err(|_| ArgumentError::InvalidValue("--scheduler", value))?;
+                },
+                "--drain" => options.drain = true,
+                "--script" => options.script = Some(args.next().ok_or(ArgumentError::MissingValue("--script"))?.into()),
+                "--abort-on-error" => options.abort_on_error = true,
+                "-h" | "--help" => options.help = true,
+                _ => return Err(ArgumentError::UnknownArgument(arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Turns the lines of a config file into command line arguments.
+///
+/// Each line holds an option without the leading `--`, followed by `= <value>` if it takes one,
+/// e.g. `threads = 64` or `dry-run`. Empty lines and lines starting with `#` are ignored.
+pub fn config_arguments(config: &str) -> Vec<String> {
+    let mut args = Vec::new();
+
+    for line in config.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((option, value)) => args.extend([format!("--{}", option.trim()), value.trim().to_string()]),
+            None => args.push(format!("--{}", line)),
+        }
+    }
+
+    args
+}
+
+fn parse_positive(arg: &'static str, value: &str) -> Result<f64, ArgumentError> {
+    match value.parse::<f64>() {
+        Ok(n) if n.is_finite() && n > 0.0 => Ok(n),
+        _ => Err(ArgumentError::InvalidValue(arg, value.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use crate::{event_log::{Pacing, Timing}, BarrierPolicy, DuplicatePolicy, RemovalPolicy, SchedulingPolicy};
+
+    use super::{config_arguments, ArgumentError, Options, Startup};
+
+    fn parse(args: &[&str]) -> Result<Options, ArgumentError> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert_eq!(parse(&[]), Ok(Options::default()));
+    }
+
+    #[test]
+    fn parse_ayudame_lib() {
+        let options = parse(&["--ayudame-lib", "/opt/ayudame/lib/libayudame.so"]).unwrap();
+        assert_eq!(options.ayudame_lib, Some(PathBuf::from("/opt/ayudame/lib/libayudame.so")));
+
+        assert_eq!(parse(&["--ayudame-lib"]), Err(ArgumentError::MissingValue("--ayudame-lib")));
+    }
+
+    #[test]
+    fn parse_dry_run() {
+        assert!(parse(&["--dry-run"]).unwrap().dry_run);
+        assert!(!parse(&[]).unwrap().dry_run);
+    }
+
+    #[test]
+    fn parse_record() {
+        assert_eq!(parse(&["--record", "session.log"]).unwrap().record, Some(PathBuf::from("session.log")));
+        assert_eq!(parse(&["--record"]), Err(ArgumentError::MissingValue("--record")));
+    }
+
+    #[test]
+    fn parse_replay() {
+        let options = parse(&["--replay", "session.log"]).unwrap();
+        assert_eq!(options.replay, Some(PathBuf::from("session.log")));
+        assert_eq!(options.pacing, Pacing::default());
+
+        let options = parse(&["--replay-speed", "2.5", "--max-rate", "100"]).unwrap();
+        assert_eq!(options.pacing, Pacing { timing: Timing::Original { speed: 2.5 }, max_rate: Some(100.0) });
+
+        let options = parse(&["--replay-speed", "max"]).unwrap();
+        assert_eq!(options.pacing.timing, Timing::AsFastAsPossible);
+
+        assert_eq!(parse(&["--replay-speed", "0"]), Err(ArgumentError::InvalidValue("--replay-speed", "0".to_string())));
+        assert_eq!(parse(&["--max-rate", "fast"]), Err(ArgumentError::InvalidValue("--max-rate", "fast".to_string())));
+    }
+
+    #[test]
+    fn parse_script() {
+        let options = parse(&["--script", "-", "--abort-on-error"]).unwrap();
+        assert_eq!(options.script, Some(PathBuf::from("-")));
+        assert!(options.abort_on_error);
+
+        assert_eq!(parse(&["--script"]), Err(ArgumentError::MissingValue("--script")));
+    }
+
+    #[test]
+    fn parse_protocol_overrides() {
+        assert!(parse(&["--chaos"]).unwrap().chaos);
+        assert!(parse(&["--allow-cycles"]).unwrap().allow_cycles);
+        assert_eq!(parse(&["--on-remove", "cascade"]).unwrap().removal_policy, RemovalPolicy::Cascade);
+        assert_eq!(parse(&["--on-remove", "keep"]), Err(ArgumentError::InvalidValue("--on-remove", "keep".to_string())));
+        assert_eq!(parse(&["--on-duplicate", "ignore"]).unwrap().duplicate_policy, DuplicatePolicy::Ignore);
+        assert_eq!(parse(&["--at-barrier", "refuse"]).unwrap().barrier_policy, BarrierPolicy::Refuse);
+        assert!(parse(&["--drain"]).unwrap().drain);
+        assert_eq!(parse(&["--scheduler", "critical"]).unwrap().scheduling_policy, SchedulingPolicy::CriticalFirst);
+    }
+
+    #[test]
+    fn parse_runtime() {
+        let options = parse(&["--runtime-id", "3", "--threads", "64"]).unwrap();
+        assert_eq!((options.runtime_id, options.n_threads), (Some(3), Some(64)));
+
+        assert_eq!(parse(&["--threads", "0"]), Err(ArgumentError::InvalidValue("--threads", "0".to_string())));
+        assert_eq!(parse(&["--threads", "4097"]), Err(ArgumentError::InvalidValue("--threads", "4097".to_string())));
+    }
+
+    #[test]
+    fn parse_startup() {
+        assert_eq!(parse(&[]).unwrap().startup, Startup::Automatic);
+        assert_eq!(parse(&["--startup", "manual"]).unwrap().startup, Startup::Manual);
+        assert_eq!(parse(&["--startup", "delay=250"]).unwrap().startup, Startup::Delayed(Duration::from_millis(250)));
+        assert_eq!(parse(&["--startup", "delay=soon"]), Err(ArgumentError::InvalidValue("--startup", "delay=soon".to_string())));
+
+        assert_eq!(parse(&["--keep-alive", "1000"]).unwrap().keep_alive, Some(Duration::from_secs(1)));
+        assert_eq!(parse(&["--keep-alive", "0"]), Err(ArgumentError::InvalidValue("--keep-alive", "0".to_string())));
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = "# emulate a bigger runtime\nthreads = 64\n\nruntime-id=3\ndry-run\n";
+        assert_eq!(config_arguments(config), ["--threads", "64", "--runtime-id", "3", "--dry-run"]);
+
+        // later arguments take precedence
+        let args = config_arguments(config).into_iter().chain(["--threads".to_string(), "8".to_string()]);
+        let options = Options::parse(args).unwrap();
+        assert_eq!((options.runtime_id, options.n_threads, options.dry_run), (Some(3), Some(8), true));
+    }
+
+    #[test]
+    fn parse_unknown() {
+        assert_eq!(parse(&["--frobnicate"]), Err(ArgumentError::UnknownArgument("--frobnicate".to_string())));
+    }
+}
@@ -0,0 +1,111 @@
+/// A small pseudo random number generator (xoshiro256**, seeded by SplitMix64).
+///
+/// Generated graphs only depend on the seed, so the same seed always gives the same graph,
+/// on every platform and independent of any external crate.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    s: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut state = seed;
+        Self { s: [(); 4].map(|_| split_mix64(&mut state)) }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.s[1] << 17;
+
+        self.s[2] ^= self.s[0];
+        self.s[3] ^= self.s[1];
+        self.s[1] ^= self.s[2];
+        self.s[0] ^= self.s[3];
+        self.s[2] ^= t;
+        self.s[3] = self.s[3].rotate_left(45);
+
+        result
+    }
+
+    /// A uniformly distributed number below `n`, which has to be positive
+    pub fn below(&mut self, n: u64) -> u64 {
+        // reject the top values which would make smaller results more likely
+        let limit = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < limit {
+                return x % n;
+            }
+        }
+    }
+
+    /// A uniformly distributed index into a slice of length `len`, which has to be positive
+    pub fn index(&mut self, len: usize) -> usize {
+        self.below(len as u64) as usize
+    }
+
+    /// A uniformly distributed number in `[0, 1)`
+    pub fn f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// True with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.f64() < p
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.index(i + 1));
+        }
+    }
+}
+
+fn split_mix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_mix64, Rng};
+
+    #[test]
+    fn reference_outputs() {
+        let mut state = 0;
+        assert_eq!(split_mix64(&mut state), 0xe220a8397b1dcdaf);
+
+        let mut rng = Rng { s: [1, 2, 3, 4] };
+        let outputs: Vec<_> = (0..4).map(|_| rng.next_u64()).collect();
+        assert_eq!(outputs, [11520, 0, 1509978240, 1215971899390074240]);
+    }
+
+    #[test]
+    fn same_seed_same_numbers() {
+        let numbers = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..16).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(7), numbers(7));
+        assert_ne!(numbers(7), numbers(8));
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            assert!(rng.below(3) < 3);
+            assert!((0.0..1.0).contains(&rng.f64()));
+        }
+        assert!(!rng.chance(0.0));
+        assert!(rng.chance(1.0));
+
+        let mut items = [0, 1, 2, 3, 4, 5];
+        rng.shuffle(&mut items);
+        items.sort();
+        assert_eq!(items, [0, 1, 2, 3, 4, 5]);
+    }
+}
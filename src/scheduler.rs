@@ -0,0 +1,301 @@
+use std::{collections::{BTreeMap, VecDeque}, str::FromStr};
+
+use crate::{command::ParseError, rng::Rng, simulator::advance, AppState, EventSink, Task, TaskState};
+
+/// Decides on which thread ready tasks are queued, and which of them a thread runs next.
+/// Schedulers can be moved to other threads, so worker threads can share one behind a lock.
+pub trait Scheduler: Send {
+    /// Adds a task that became ready on thread `thread_id`, and returns the thread it is queued on
+    fn push(&mut self, task: ReadyTask, thread_id: u64) -> u64;
+    /// Takes the task thread `thread_id` runs next, if there is one it may run
+    fn pop(&mut self, thread_id: u64) -> Option<u64>;
+}
+
+/// What a scheduler gets to know about a ready task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadyTask {
+    pub id: u64,
+    pub is_critical: bool,
+    /// The thread the task was created for
+    pub thread_id: u64,
+}
+
+impl From<&Task> for ReadyTask {
+    fn from(task: &Task) -> Self {
+        let (id, _, _, thread_id) = task.into_raw_parts();
+        Self { id, is_critical: task.is_critical(), thread_id }
+    }
+}
+
+/// The scheduling policies that can be chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SchedulingPolicy {
+    /// One queue for all threads, the task queued first runs first
+    #[default]
+    Fifo,
+    /// One stack for all threads, the task queued last runs first
+    Lifo,
+    /// Like FIFO, but critical tasks run before all others
+    CriticalFirst,
+    /// Every task runs on the thread it was created for
+    Affinity,
+    /// Every thread runs the tasks it queued last first, and steals the oldest task of a random thread when it has none
+    WorkStealing,
+}
+
+impl SchedulingPolicy {
+    /// A scheduler with this policy for `n_threads` threads, whose queues are empty
+    pub fn scheduler(self, n_threads: u64) -> Box<dyn Scheduler> {
+        let n_threads = n_threads.max(1);
+
+        match self {
+            SchedulingPolicy::Fifo => Box::new(Fifo::default()),
+            SchedulingPolicy::Lifo => Box::new(Lifo::default()),
+            SchedulingPolicy::CriticalFirst => Box::new(CriticalFirst::default()),
+            SchedulingPolicy::Affinity => Box::new(Affinity { n_threads, queues: BTreeMap::new() }),
+            // a fixed seed, so the same graph is always scheduled the same way
+            SchedulingPolicy::WorkStealing => Box::new(WorkStealing { n_threads, deques: BTreeMap::new(), rng: Rng::new(0) }),
+        }
+    }
+}
+
+impl FromStr for SchedulingPolicy {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fifo" => Ok(SchedulingPolicy::Fifo),
+            "lifo" => Ok(SchedulingPolicy::Lifo),
+            "critical" => Ok(SchedulingPolicy::CriticalFirst),
+            "affinity" => Ok(SchedulingPolicy::Affinity),
+            "steal" => Ok(SchedulingPolicy::WorkStealing),
+            _ => Err(ParseError::InvalidArgument(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Fifo {
+    queue: VecDeque<u64>,
+}
+
+impl Scheduler for Fifo {
+    fn push(&mut self, task: ReadyTask, thread_id: u64) -> u64 {
+        self.queue.push_back(task.id);
+        thread_id
+    }
+
+    fn pop(&mut self, _: u64) -> Option<u64> {
+        self.queue.pop_front()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Lifo {
+    stack: Vec<u64>,
+}
+
+impl Scheduler for Lifo {
+    fn push(&mut self, task: ReadyTask, thread_id: u64) -> u64 {
+        self.stack.push(task.id);
+        thread_id
+    }
+
+    fn pop(&mut self, _: u64) -> Option<u64> {
+        self.stack.pop()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CriticalFirst {
+    critical: VecDeque<u64>,
+    others: VecDeque<u64>,
+}
+
+impl Scheduler for CriticalFirst {
+    fn push(&mut self, task: ReadyTask, thread_id: u64) -> u64 {
+        match task.is_critical {
+            true => self.critical.push_back(task.id),
+            false => self.others.push_back(task.id),
+        }
+        thread_id
+    }
+
+    fn pop(&mut self, _: u64) -> Option<u64> {
+        self.critical.pop_front().or_else(|| self.others.pop_front())
+    }
+}
+
+/// One queue per thread, threads never take tasks of other threads
+#[derive(Debug)]
+pub struct Affinity {
+    n_threads: u64,
+    /// Only the threads that have tasks queued have a queue
+    queues: BTreeMap<u64, VecDeque<u64>>,
+}
+
+impl Scheduler for Affinity {
+    fn push(&mut self, task: ReadyTask, _: u64) -> u64 {
+        // the thread count may have changed since the task was created
+        let thread_id = task.thread_id % self.n_threads;
+        self.queues.entry(thread_id).or_default().push_back(task.id);
+        thread_id
+    }
+
+    fn pop(&mut self, thread_id: u64) -> Option<u64> {
+        pop_from(&mut self.queues, thread_id, VecDeque::pop_front)
+    }
+}
+
+#[derive(Debug)]
+pub struct WorkStealing {
+    n_threads: u64,
+    /// Only the threads that have tasks queued have a deque
+    deques: BTreeMap<u64, VecDeque<u64>>,
+    rng: Rng,
+}
+
+impl Scheduler for WorkStealing {
+    fn push(&mut self, task: ReadyTask, thread_id: u64) -> u64 {
+        let thread_id = thread_id % self.n_threads;
+        self.deques.entry(thread_id).or_default().push_back(task.id);
+        thread_id
+    }
+
+    fn pop(&mut self, thread_id: u64) -> Option<u64> {
+        if let Some(task_id) = pop_from(&mut self.deques, thread_id, VecDeque::pop_back) {
+            return Some(task_id);
+        }
+
+        let mut victims: Vec<_> = self.deques.keys().copied().filter(|&t| t != thread_id).collect();
+        self.rng.shuffle(&mut victims);
+        victims.into_iter().find_map(|victim| pop_from(&mut self.deques, victim, VecDeque::pop_front))
+    }
+}
+
+/// Takes a task from the queue of `thread_id` with `pop`, and drops the queue once it is empty
+fn pop_from(queues: &mut BTreeMap<u64, VecDeque<u64>>, thread_id: u64, pop: fn(&mut VecDeque<u64>) -> Option<u64>) -> Option<u64> {
+    let queue = queues.get_mut(&thread_id)?;
+    let task_id = pop(queue);
+    if queue.is_empty() {
+        queues.remove(&thread_id);
+    }
+    task_id
+}
+
+/// Hands every ready task in `state` to a thread chosen by `scheduler`, sending the events for queueing and pre-running it.
+/// Tasks are queued on their own thread, then the threads take turns to pick the tasks they run, until none is left.
+/// Returns the ids of the tasks with the thread they run on, in the order they were picked.
+pub fn schedule(state: &AppState, sink: &mut dyn EventSink, scheduler: &mut dyn Scheduler) -> Vec<(u64, u64)> {
+    let ready = state.ready_tasks();
+    for &task_id in &ready {
+        let task = state.get_task(task_id).expect("ready tasks exist");
+        let thread_id = scheduler.push(task.as_ref().into(), task.into_raw_parts().3);
+        advance(state, sink, task_id, thread_id, TaskState::Queued);
+    }
+
+    let mut picked = Vec::with_capacity(ready.len());
+    while picked.len() < ready.len() {
+        let before = picked.len();
+        for thread_id in 0..state.n_threads {
+            if let Some(task_id) = scheduler.pop(thread_id) {
+                advance(state, sink, task_id, thread_id, TaskState::PreRun);
+                picked.push((task_id, thread_id));
+            }
+        }
+        // only happens if tasks were queued on threads that do not exist
+        if picked.len() == before {
+            break;
+        }
+    }
+
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::ParseError, AppState, PrintSink, TaskState};
+
+    use super::{schedule, ReadyTask, SchedulingPolicy};
+
+    /// Four ready tasks on thread 0, of which task 2 is critical, and a fifth depending on task 0
+    fn state() -> AppState {
+        let mut state = AppState::new();
+        for id in 0..5 {
+            state.create_task(id == 2, None, 0).unwrap();
+        }
+        state.add_dependency(0, 4).unwrap();
+        state
+    }
+
+    fn picked(policy: SchedulingPolicy) -> Vec<(u64, u64)> {
+        let state = state();
+        let mut sink = PrintSink::new(Vec::new());
+        let picked = schedule(&state, &mut sink, policy.scheduler(state.n_threads).as_mut());
+
+        assert_eq!(state.get_task(4).unwrap().get_state(), TaskState::Added);
+        picked
+    }
+
+    #[test]
+    fn central_queues() {
+        assert_eq!(picked(SchedulingPolicy::Fifo), [(0, 0), (1, 1), (2, 0), (3, 1)]);
+        assert_eq!(picked(SchedulingPolicy::Lifo), [(3, 0), (2, 1), (1, 0), (0, 1)]);
+        assert_eq!(picked(SchedulingPolicy::CriticalFirst), [(2, 0), (0, 1), (1, 0), (3, 1)]);
+    }
+
+    #[test]
+    fn affinity_keeps_tasks_on_their_thread() {
+        assert_eq!(picked(SchedulingPolicy::Affinity), [(0, 0), (1, 0), (2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn work_stealing() {
+        // thread 0 runs its newest task, thread 1 steals the oldest one
+        assert_eq!(picked(SchedulingPolicy::WorkStealing), [(3, 0), (0, 1), (2, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn queues_are_only_kept_for_threads_with_tasks() {
+        for policy in [SchedulingPolicy::Affinity, SchedulingPolicy::WorkStealing] {
+            let mut scheduler = policy.scheduler(u64::MAX);
+            let thread_id = u64::MAX - 1;
+
+            assert_eq!(scheduler.push(ReadyTask { id: 7, is_critical: false, thread_id }, thread_id), thread_id);
+            assert_eq!(scheduler.pop(0), if policy == SchedulingPolicy::WorkStealing { Some(7) } else { None }, "{:?}", policy);
+            assert_eq!(scheduler.pop(thread_id), if policy == SchedulingPolicy::Affinity { Some(7) } else { None }, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn schedule_sends_queue_and_pre_run() {
+        let state = state();
+        state.transition_task(0, TaskState::Queued).unwrap();
+        state.transition_task(0, TaskState::PreRun).unwrap();
+        state.transition_task(0, TaskState::Running).unwrap();
+        state.transition_task(0, TaskState::PostRun).unwrap();
+        state.transition_task(1, TaskState::Queued).unwrap();
+
+        let mut sink = PrintSink::new(Vec::new());
+        let picked = schedule(&state, &mut sink, SchedulingPolicy::Lifo.scheduler(2).as_mut());
+        assert_eq!(picked, [(4, 0), (3, 1), (2, 0), (1, 1)]);
+
+        // task 1 was queued before, task 4 is ready now that task 0 has finished
+        let events = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(events, "\
+ayu_event_addtasktoqueue task_id=2 thread_id=0
+ayu_event_addtasktoqueue task_id=3 thread_id=0
+ayu_event_addtasktoqueue task_id=4 thread_id=0
+ayu_event_preruntask task_id=4 thread_id=0
+ayu_event_preruntask task_id=3 thread_id=1
+ayu_event_preruntask task_id=2 thread_id=0
+ayu_event_preruntask task_id=1 thread_id=1
+");
+    }
+
+    #[test]
+    fn scheduling_policy_parse() {
+        assert_eq!("critical".parse(), Ok(SchedulingPolicy::CriticalFirst));
+        assert_eq!("random".parse::<SchedulingPolicy>(), Err(ParseError::InvalidArgument("random".to_string())));
+    }
+}
@@ -0,0 +1,328 @@
+use std::{cmp::Reverse, collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap}, fmt::Display, str::FromStr};
+
+use crate::{command::ParseError, AppState, EventSink, RemovalPolicy, Scheduler, Task, TaskState};
+
+/// How long tasks run, in ticks of virtual time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Durations {
+    /// Duration of tasks without one of their own or of their label
+    pub default: u64,
+    /// Durations of the tasks with a label, by its name
+    pub labels: BTreeMap<String, u64>,
+    /// Durations of single tasks, by their id
+    pub tasks: BTreeMap<u64, u64>,
+}
+
+impl Default for Durations {
+    fn default() -> Self {
+        Self { default: 1, labels: BTreeMap::new(), tasks: BTreeMap::new() }
+    }
+}
+
+impl Durations {
+    pub fn of(&self, task: &Task) -> u64 {
+        let label = task.get_function().and_then(|f| f.c_name().to_str().ok().and_then(|name| self.labels.get(name)).copied());
+        self.tasks.get(&task.get_id()).copied().or(label).unwrap_or(self.default)
+    }
+}
+
+/// Parses `[duration=<ticks>] [<task id>=<ticks> ...] [<label>=<ticks> ...]`
+impl FromStr for Durations {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut durations = Durations::default();
+
+        for word in s.split_whitespace() {
+            let (key, value) = word.split_once('=').ok_or_else(|| ParseError::UnexpectedArgument(word.to_string()))?;
+            let ticks = value.parse().map_err(|_| ParseError::InvalidArgument(word.to_string()))?;
+
+            if key == "duration" {
+                durations.default = ticks;
+            } else if let Ok(task_id) = key.parse() {
+                durations.tasks.insert(task_id, ticks);
+            } else {
+                durations.labels.insert(key.to_string(), ticks);
+            }
+        }
+
+        Ok(durations)
+    }
+}
+
+/// The outcome of a simulation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulationReport {
+    /// Number of tasks that ran
+    pub tasks: usize,
+    /// Virtual time at which the last task finished
+    pub makespan: u64,
+    /// Ticks each thread spent running tasks
+    pub busy: Vec<u64>,
+    /// Tasks that never became ready, because they depend on each other in a cycle
+    pub blocked: Vec<u64>,
+}
+
+impl SimulationReport {
+    /// The share of the makespan each thread spent running tasks
+    pub fn utilisation(&self) -> Vec<f64> {
+        self.busy.iter().map(|&busy| if self.makespan == 0 { 0.0 } else { busy as f64 / self.makespan as f64 }).collect()
+    }
+}
+
+impl Display for SimulationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Simulated {} tasks on {} threads, makespan: {} ticks", self.tasks, self.busy.len(), self.makespan)?;
+        for (thread_id, (busy, utilisation)) in self.busy.iter().zip(self.utilisation()).enumerate() {
+            write!(f, "\n\tThread {}: busy for {} ticks, utilisation {:.1}%", thread_id, busy, utilisation * 100.0)?;
+        }
+        if !self.blocked.is_empty() {
+            let ids: Vec<_> = self.blocked.iter().map(|id| id.to_string()).collect();
+            write!(f, "\n\tTasks {} never became ready.", ids.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs all unfinished tasks in `state` in virtual time, on `state.n_threads` threads, sending their events to `sink`.
+///
+/// A task is handed to `scheduler` once all tasks it depends on have finished, by the thread that finished the last of them,
+/// or by its own thread if it is ready from the start. Idle threads take the tasks the scheduler gives them,
+/// and every task is removed right after it has run. Tasks that were already queued or started before
+/// only get their remaining events, and are handed to the scheduler first.
+pub fn simulate(state: &mut AppState, sink: &mut dyn EventSink, durations: &Durations, scheduler: &mut dyn Scheduler) -> SimulationReport {
+    let PendingTasks { task_ids, children, mut waiting_for, ready } = PendingTasks::of(state);
+    for id in ready {
+        let task = state.get_task(id).expect("unfinished tasks exist");
+        let thread_id = scheduler.push(task.as_ref().into(), task.into_raw_parts().3);
+        advance(state, sink, id, thread_id, TaskState::Queued);
+    }
+
+    let mut time: u64 = 0;
+    let mut idle: BTreeSet<u64> = (0..state.n_threads).collect();
+    let mut busy = vec![0u64; state.n_threads as usize];
+    // tasks that are running, by the time they end and the thread they run on
+    let mut running = BinaryHeap::new();
+    let mut finished = 0;
+
+    loop {
+        for thread_id in idle.clone() {
+            let Some(task_id) = scheduler.pop(thread_id) else {
+                continue;
+            };
+            idle.remove(&thread_id);
+            let duration = state.get_task(task_id).map_or(durations.default, |t| durations.of(t));
+
+            advance(state, sink, task_id, thread_id, TaskState::Running);
+            // durations are given by the user, so the clock stops at the end of time instead of overflowing
+            running.push(Reverse((time.saturating_add(duration), thread_id, task_id)));
+            busy[thread_id as usize] = busy[thread_id as usize].saturating_add(duration);
+        }
+
+        let Some(Reverse((end, thread_id, task_id))) = running.pop() else {
+            break;
+        };
+        time = end;
+
+        advance(state, sink, task_id, thread_id, TaskState::PostRun);
+        if state.remove_task(task_id, RemovalPolicy::Detach).is_ok() {
+            sink.remove_task(task_id);
+        }
+        idle.insert(thread_id);
+        finished += 1;
+
+        for &child in &children[&task_id] {
+            let Some(count) = waiting_for.get_mut(&child) else {
+                continue;
+            };
+            *count -= 1;
+            if *count == 0 {
+                let task = state.get_task(child).expect("unfinished tasks exist");
+                let queue_thread = scheduler.push(task.as_ref().into(), thread_id);
+                advance(state, sink, child, queue_thread, TaskState::Queued);
+            }
+        }
+    }
+
+    let blocked = task_ids.into_iter().filter(|id| state.does_task_exist(*id)).collect();
+    SimulationReport { tasks: finished, makespan: time, busy, blocked }
+}
+
+/// The unfinished tasks of a graph, and how they depend on each other
+pub(crate) struct PendingTasks {
+    pub task_ids: Vec<u64>,
+    /// The children of every unfinished task
+    pub children: HashMap<u64, Vec<u64>>,
+    /// The number of unfinished parents of every task that has not started yet
+    pub waiting_for: HashMap<u64, usize>,
+    /// The tasks that are ready or have started already, the started ones first
+    pub ready: Vec<u64>,
+}
+
+impl PendingTasks {
+    pub(crate) fn of(state: &AppState) -> Self {
+        let task_ids: Vec<_> = state.task_ids().into_iter()
+            .filter(|&id| state.get_task(id).is_some_and(|t| !t.get_state().is_finished()))
+            .collect();
+
+        let mut children = HashMap::new();
+        let mut waiting_for: HashMap<u64, usize> = task_ids.iter().map(|&id| (id, 0)).collect();
+        for &id in &task_ids {
+            let task_children = state.get_children(id).unwrap_or_default();
+            for child in &task_children {
+                if let Some(count) = waiting_for.get_mut(child) {
+                    *count += 1;
+                }
+            }
+            children.insert(id, task_children);
+        }
+
+        let mut ready = Vec::new();
+        for &id in &task_ids {
+            let task = state.get_task(id).expect("unfinished tasks exist");
+            match task.get_state() {
+                TaskState::Added if waiting_for[&id] == 0 => ready.push(id),
+                TaskState::Added => (),
+                // started tasks are ready, whatever they depend on
+                _ => {
+                    waiting_for.remove(&id);
+                    ready.push(id);
+                },
+            }
+        }
+        // started tasks go first
+        ready.sort_by_key(|&id| state.get_task(id).is_some_and(|t| t.get_state() == TaskState::Added));
+
+        Self { task_ids, children, waiting_for, ready }
+    }
+}
+
+/// Sends the events moving the task with `task_id` on to `to`, skipping the states it has been in already
+pub(crate) fn advance(state: &AppState, sink: &mut dyn EventSink, task_id: u64, thread_id: u64, to: TaskState) {
+    while let Some(from) = state.get_task(task_id).map(|t| t.get_state()).filter(|s| *s < to) {
+        let next = from.successors()[0];
+        state.transition_task(task_id, next).expect("tasks move on to their first successor");
+
+        match next {
+            TaskState::Queued => sink.add_task_to_queue(task_id, thread_id),
+            TaskState::PreRun => sink.pre_run_task(task_id, thread_id),
+            TaskState::Running => sink.run_task(task_id),
+            _ => sink.post_run_task(task_id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command::ParseError, AppState, PrintSink, SchedulingPolicy, TaskState};
+
+    use super::{simulate, Durations, SimulationReport};
+
+    /// Runs the simulation with tasks 0 and 1 on thread 0 both followed by task 2, returning the report and the events
+    fn diamond(n_threads: u64, durations: &str, policy: SchedulingPolicy) -> (SimulationReport, String) {
+        let mut state = AppState::new();
+        state.n_threads = n_threads;
+        let function = state.create_function("kernel".to_string()).unwrap();
+        for _ in 0..3 {
+            state.create_task(false, Some(function.id), 0).unwrap();
+        }
+        state.add_dependency(0, 2).unwrap();
+        state.add_dependency(1, 2).unwrap();
+
+        let mut sink = PrintSink::new(Vec::new());
+        let report = simulate(&mut state, &mut sink, &durations.parse().unwrap(), policy.scheduler(n_threads).as_mut());
+        assert!(state.task_ids().is_empty());
+
+        (report, String::from_utf8(sink.into_inner()).unwrap())
+    }
+
+    #[test]
+    fn simulate_respects_dependencies() {
+        let (report, events) = diamond(2, "duration=2 1=3", SchedulingPolicy::Fifo);
+        assert_eq!(events, "\
+ayu_event_addtasktoqueue task_id=0 thread_id=0
+ayu_event_addtasktoqueue task_id=1 thread_id=0
+ayu_event_preruntask task_id=0 thread_id=0
+ayu_event_runtask task_id=0
+ayu_event_preruntask task_id=1 thread_id=1
+ayu_event_runtask task_id=1
+ayu_event_postruntask task_id=0
+ayu_event_removetask task_id=0
+ayu_event_postruntask task_id=1
+ayu_event_removetask task_id=1
+ayu_event_addtasktoqueue task_id=2 thread_id=1
+ayu_event_preruntask task_id=2 thread_id=0
+ayu_event_runtask task_id=2
+ayu_event_postruntask task_id=2
+ayu_event_removetask task_id=2
+");
+        assert_eq!(report, SimulationReport { tasks: 3, makespan: 5, busy: vec![4, 3], blocked: Vec::new() });
+        assert_eq!(report.to_string(), "\
+Simulated 3 tasks on 2 threads, makespan: 5 ticks
+\tThread 0: busy for 4 ticks, utilisation 80.0%
+\tThread 1: busy for 3 ticks, utilisation 60.0%");
+    }
+
+    #[test]
+    fn simulate_on_one_thread() {
+        let (report, _) = diamond(1, "kernel=4", SchedulingPolicy::Fifo);
+        assert_eq!(report.makespan, 12);
+        assert_eq!(report.utilisation(), [1.0]);
+    }
+
+    #[test]
+    fn simulate_with_affinity() {
+        // all tasks were created for thread 0, so thread 1 stays idle
+        let (report, events) = diamond(2, "duration=2 1=3", SchedulingPolicy::Affinity);
+        assert_eq!(report.makespan, 7);
+        assert_eq!(report.busy, [7, 0]);
+        assert!(!events.contains("thread_id=1"));
+    }
+
+    #[test]
+    fn simulate_saturates_time() {
+        let (report, _) = diamond(1, "duration=18446744073709551615", SchedulingPolicy::Fifo);
+        assert_eq!(report.tasks, 3);
+        assert_eq!(report.makespan, u64::MAX);
+        assert_eq!(report.busy, [u64::MAX]);
+    }
+
+    #[test]
+    fn simulate_continues_started_tasks() {
+        let mut state = AppState::new();
+        for _ in 0..3 {
+            state.create_task(false, None, 1).unwrap();
+        }
+        state.add_dependency(0, 1).unwrap();
+        state.transition_task(2, TaskState::Queued).unwrap();
+        state.transition_task(2, TaskState::PreRun).unwrap();
+        state.add_dependency(1, 2).unwrap();
+        // a cycle is never ready
+        state.allow_cycles = true;
+        let cycle: Vec<_> = (0..2).map(|_| state.create_task(false, None, 0).unwrap().get_id()).collect();
+        state.add_dependency(cycle[0], cycle[1]).unwrap();
+        state.add_dependency(cycle[1], cycle[0]).unwrap();
+
+        let mut sink = PrintSink::new(Vec::new());
+        let report = simulate(&mut state, &mut sink, &Durations::default(), SchedulingPolicy::Fifo.scheduler(2).as_mut());
+        let events = String::from_utf8(sink.into_inner()).unwrap();
+
+        // task 2 only needs to run, and goes before task 0 although it depends on it indirectly
+        assert!(events.starts_with("ayu_event_addtasktoqueue task_id=0 thread_id=1\nayu_event_runtask task_id=2\n"));
+        assert_eq!(report.tasks, 3);
+        assert_eq!(report.blocked, cycle);
+        assert!(report.to_string().ends_with("\n\tTasks 3, 4 never became ready."));
+    }
+
+    #[test]
+    fn durations_parse() {
+        let durations: Durations = "duration=3 7=10 POTRF=5".parse().unwrap();
+        assert_eq!(durations.default, 3);
+        assert_eq!(durations.tasks.get(&7), Some(&10));
+        assert_eq!(durations.labels.get("POTRF"), Some(&5));
+
+        assert_eq!("".parse(), Ok(Durations::default()));
+        assert_eq!("duration".parse::<Durations>(), Err(ParseError::UnexpectedArgument("duration".to_string())));
+        assert_eq!("GEMM=fast".parse::<Durations>(), Err(ParseError::InvalidArgument("GEMM=fast".to_string())));
+    }
+}
@@ -0,0 +1,67 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use common::run_wrapper;
+
+const VIOLATIONS: &str = "\
+run 0
+task
+task id=0
+dep 0 -> 5
+init
+finish
+queue 0
+";
+
+#[test]
+fn violations_are_refused_without_chaos() {
+    let run = run_wrapper("violations_are_refused_without_chaos", &["--script", "-"], VIOLATIONS);
+
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_addtask task_id=0 func_id=0 priority=0 scope_id=0",
+        "ayu_event_finish",
+    ]);
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:3: Error while reading input:\n\tTask with id: 0 already exists."));
+    assert!(stderr.contains("<stdin>:7: Error while reading input:\n\tFinish was already sent, no more events are allowed."));
+}
+
+#[test]
+fn chaos_sends_violations() {
+    let run = run_wrapper("chaos_sends_violations", &["--chaos", "--script", "-"], VIOLATIONS);
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_runtask task_id=0",
+        "ayu_event_addtask task_id=0 func_id=0 priority=0 scope_id=0",
+        "ayu_event_addtask task_id=0 func_id=0 priority=0 scope_id=0",
+        "ayu_event_adddependency to_id=0 from_id=5 memaddr=0xffffeeee orig_memaddr=0xffffeef",
+        "ayu_event_init n_threads=2",
+        "ayu_event_finish",
+        "ayu_event_addtasktoqueue task_id=0 thread_id=0",
+    ]);
+
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    for rule in ["events refer to existing tasks", "task ids are unique", "pre_init and init are sent once", "no events after finish"] {
+        assert!(stderr.contains(&format!("Chaos: sending anyway, breaking rule \"{}\"", rule)), "{} not reported", rule);
+    }
+}
+
+#[test]
+fn chaos_keeps_forced_tasks() {
+    let run = run_wrapper("chaos_keeps_forced_tasks", &["--chaos", "--script", "-"], "function kernel\ntask label=4\nqueue 0\n");
+
+    assert!(run.output.status.success());
+    assert_eq!(&run.calls[3..5], [
+        "ayu_event_addtask task_id=0 func_id=4 priority=0 scope_id=0",
+        "ayu_event_addtasktoqueue task_id=0 thread_id=0",
+    ]);
+    // the task exists although its label does not, so only adding it broke a rule
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert_eq!(stderr.matches("Chaos: sending anyway").count(), 1);
+}
@@ -0,0 +1,81 @@
+//! Helpers to run the wrapper binary against the fake libayudame in `fake_ayudame/`
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::OnceLock,
+};
+
+/// Builds the fake libayudame (once per test binary) and returns the path to it
+pub fn fake_ayudame() -> &'static Path {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+    PATH.get_or_init(|| {
+        // use a separate target dir, the main one is locked by the cargo process running the tests
+        let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fake_ayudame");
+        let status = Command::new(env!("CARGO"))
+            .args(["build", "--quiet", "--package", "fake_ayudame", "--target-dir"])
+            .arg(&target_dir)
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .status()
+            .expect("unable to run cargo");
+        assert!(status.success(), "building fake_ayudame failed");
+
+        target_dir.join("debug").join(format!("{}ayudame{}", std::env::consts::DLL_PREFIX, std::env::consts::DLL_SUFFIX))
+    })
+}
+
+/// The result of running the wrapper against the fake libayudame
+pub struct Run {
+    pub output: Output,
+    /// Every call the wrapper made into the library, one per line
+    pub calls: Vec<String>,
+}
+
+/// Runs the wrapper with `args`, feeding `stdin` to it, and collects the calls it made
+pub fn run_wrapper(name: &str, args: &[&str], stdin: &str) -> Run {
+    let log = Path::new(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.log", name));
+    let _ = fs::remove_file(&log);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ayudame_wrapper"))
+        .arg("--ayudame-lib")
+        .arg(fake_ayudame())
+        .args(args)
+        .env("FAKE_AYUDAME_LOG", &log)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("unable to start ayudame_wrapper");
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let calls = fs::read_to_string(&log)
+        .unwrap_or_default()
+        .lines()
+        .map(str::to_string)
+        .collect();
+
+    Run { output, calls }
+}
+
+/// The index of the first `event` call for `task_id`, whatever the arguments after the id are
+#[allow(dead_code)] // not every test binary uses it
+pub fn position(calls: &[String], event: &str, task_id: u64) -> Option<usize> {
+    let call = format!("ayu_event_{} task_id={}", event, task_id);
+    calls.iter().position(|c| *c == call || c.strip_prefix(&call).is_some_and(|args| args.starts_with(' ')))
+}
+
+/// Asserts that the child of every dependency that was sent was only queued after its parent was post-run
+#[allow(dead_code)] // not every test binary uses it
+pub fn assert_parents_finish_first(calls: &[String]) {
+    for dependency in calls.iter().filter(|c| c.starts_with("ayu_event_adddependency")) {
+        let ids: Vec<u64> = dependency.split(' ').skip(1).take(2).map(|arg| arg.split_once('=').unwrap().1.parse().unwrap()).collect();
+        let parent_done = position(calls, "postruntask", ids[0]).unwrap();
+        let child_queued = position(calls, "addtasktoqueue", ids[1]).unwrap();
+        assert!(parent_done < child_queued, "task {} was queued before task {} finished", ids[1], ids[0]);
+    }
+}
@@ -0,0 +1,78 @@
+//! Sessions that print their events instead of loading libayudame, so they also run without the `libayudame` feature
+
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+const SCRIPT: &str = "function kernel\ntask crit thread=1 label=0\ntask\ndep 0 -> 1\nrun 7\nqueue 0\nfinish\n";
+
+/// Runs the wrapper in dry-run mode with `args`, feeding `stdin` to it, and returns its output with the events it printed
+fn dry_run(args: &[&str], stdin: &str) -> (Output, Vec<String>) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ayudame_wrapper"))
+        .arg("--dry-run")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("unable to start ayudame_wrapper");
+
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let events = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.starts_with("ayu_event_"))
+        .map(str::to_string)
+        .collect();
+    (output, events)
+}
+
+#[test]
+fn dry_run_prints_events() {
+    let (output, events) = dry_run(&["--script", "-"], SCRIPT);
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(events, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_registerfunction func_id=0 name=\"kernel\"",
+        "ayu_event_addtask task_id=0 func_id=0 priority=1 scope_id=1",
+        "ayu_event_addtask task_id=1 func_id=1 priority=0 scope_id=0",
+        "ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef",
+        "ayu_event_addtasktoqueue task_id=0 thread_id=1",
+        "ayu_event_finish",
+    ]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("<stdin>:5: Error while reading input:\n\tTask with id: 7 not found."));
+}
+
+#[test]
+fn dry_run_records_and_replays() {
+    let log = Path::new(env!("CARGO_TARGET_TMPDIR")).join("dry_run_records_and_replays.events");
+    let _ = fs::remove_file(&log);
+
+    let (_, printed) = dry_run(&["--script", "-", "--record", log.to_str().unwrap()], SCRIPT);
+    let recorded: Vec<_> = fs::read_to_string(&log).unwrap()
+        .lines()
+        .skip(1)
+        .map(|line| line.splitn(3, ' ').nth(2).unwrap().to_string())
+        .collect();
+    assert_eq!(recorded, printed);
+
+    let (output, replayed) = dry_run(&["--replay", log.to_str().unwrap(), "--replay-speed", "max"], "");
+    assert!(output.status.success());
+    assert_eq!(replayed, printed);
+}
+
+#[test]
+fn dry_run_executes_on_workers() {
+    let (output, events) = dry_run(&["--script", "-"], "generate forkjoin width=4 depth=2\nexecute tick=0\n");
+
+    assert!(output.status.success());
+    // the events of the workers are printed by the main thread
+    assert_eq!(events.iter().filter(|e| e.starts_with("ayu_event_removetask")).count(), 26);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Executed 26 tasks on 2 workers in "));
+}
@@ -0,0 +1,24 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use common::{assert_parents_finish_first, position, run_wrapper};
+
+#[test]
+fn execute_runs_parents_first() {
+    let script = "generate cholesky tiles=4\nexecute tick=1 duration=2 GEMM=5\n";
+    let run = run_wrapper("execute_runs_parents_first", &["--script", "-", "--threads", "4", "--scheduler", "steal"], script);
+
+    assert!(run.output.status.success());
+    assert_parents_finish_first(&run.calls);
+
+    // every task gets its whole lifecycle, in order
+    for task_id in 0..20 {
+        let lifecycle: Vec<_> = ["addtasktoqueue", "preruntask", "runtask", "postruntask", "removetask"].iter()
+            .map(|event| position(&run.calls, event, task_id).unwrap())
+            .collect();
+        assert!(lifecycle.windows(2).all(|w| w[0] < w[1]), "events of task {} are out of order", task_id);
+    }
+    assert_eq!(run.calls.iter().filter(|c| c.starts_with("ayu_event_removetask")).count(), 20);
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("Executed 20 tasks on 4 workers in "));
+}
@@ -0,0 +1,114 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use common::run_wrapper;
+
+#[test]
+fn startup_sends_pre_init_and_init() {
+    let run = run_wrapper("startup_sends_pre_init_and_init", &[], "");
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls, ["ayu_event_preinit rt=0", "ayu_event_init n_threads=2", "ayu_event_finish"]);
+}
+
+#[test]
+fn startup_uses_configured_runtime() {
+    let config = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("startup_uses_configured_runtime.conf");
+    std::fs::write(&config, "# a bigger runtime\nruntime-id = 3\nthreads = 4\n").unwrap();
+
+    let input = "task thread=3\ntask thread=4\n";
+    let run = run_wrapper("startup_uses_configured_runtime", &["--config", config.to_str().unwrap(), "--threads", "64"], input);
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=3",
+        "ayu_event_init n_threads=64",
+        "ayu_event_addtask task_id=0 func_id=0 priority=0 scope_id=3",
+        "ayu_event_addtask task_id=1 func_id=1 priority=0 scope_id=4",
+        "ayu_event_finish",
+    ]);
+
+    let run = run_wrapper("startup_uses_configured_runtime", &["--config", config.to_str().unwrap()], input);
+    assert_eq!(run.output.status.code(), Some(1));
+    assert_eq!(run.calls.len(), 4);
+    assert!(String::from_utf8_lossy(&run.output.stderr).contains("Thread id: 4 is out of range, the runtime has 4 threads."));
+}
+
+#[test]
+fn interactive_session_sends_events_in_order() {
+    let input = "\
+a\n3\nkernel\n\
+a\n2\ny\n1\n0\n\
+a\n2\n\n\n\n\
+a\n4\n0\n1\n\
+a\n5\n0\n\
+a\n7\n0\n\
+a\n8\n0\n\
+a\n13\n";
+    let run = run_wrapper("interactive_session_sends_events_in_order", &[], input);
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_registerfunction func_id=0 name=\"kernel\"",
+        "ayu_event_addtask task_id=0 func_id=0 priority=1 scope_id=1",
+        "ayu_event_addtask task_id=1 func_id=1 priority=0 scope_id=0",
+        "ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef",
+        "ayu_event_addtasktoqueue task_id=0 thread_id=1",
+        "ayu_event_preruntask task_id=0 thread_id=1",
+        "ayu_event_runtask task_id=0",
+        "ayu_event_finish",
+    ]);
+}
+
+#[test]
+fn unknown_task_is_not_sent() {
+    let run = run_wrapper("unknown_task_is_not_sent", &[], "a\n8\n7\n");
+
+    assert_eq!(run.output.status.code(), Some(1));
+    assert_eq!(run.calls, ["ayu_event_preinit rt=0", "ayu_event_init n_threads=2", "ayu_event_finish"]);
+    assert!(String::from_utf8_lossy(&run.output.stderr).contains("Task with id: 7 not found."));
+}
+
+#[test]
+fn interactive_one_line_commands() {
+    // `task` and `run` without arguments fall back to asking for them
+    let input = "function kernel\ntask crit thread=1 label=0\ntask\n\n\n\ndep 0 -> 1\nqueue 1\nprerun 1\nrun\n1\nremove 0\n";
+    let run = run_wrapper("interactive_one_line_commands", &[], input);
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_registerfunction func_id=0 name=\"kernel\"",
+        "ayu_event_addtask task_id=0 func_id=0 priority=1 scope_id=1",
+        "ayu_event_addtask task_id=1 func_id=1 priority=0 scope_id=0",
+        "ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef",
+        "ayu_event_addtasktoqueue task_id=1 thread_id=0",
+        "ayu_event_preruntask task_id=1 thread_id=0",
+        "ayu_event_runtask task_id=1",
+        "ayu_event_removetask task_id=0",
+        "ayu_event_finish",
+    ]);
+}
+
+#[test]
+fn illegal_lifecycle_is_not_sent() {
+    let input = "task thread=0\nrun 0\nqueue 0\nqueue 0\n";
+    let run = run_wrapper("illegal_lifecycle_is_not_sent", &[], input);
+
+    assert_eq!(run.output.status.code(), Some(1));
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_addtask task_id=0 func_id=0 priority=0 scope_id=0",
+        "ayu_event_addtasktoqueue task_id=0 thread_id=0",
+        "ayu_event_finish",
+    ]);
+
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("Task 0 cannot go from added to running, it can only become queued or removed."));
+    assert!(stderr.contains("Task 0 cannot go from queued to queued, it can only become pre-run."));
+}
@@ -0,0 +1,99 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use common::run_wrapper;
+
+#[test]
+fn generate_random_graph() {
+    let script = "generate layered tasks=12 layers=3 labels=2 seed=5\n";
+    let run = run_wrapper("generate_random_graph", &["--script", "-"], script);
+
+    assert!(run.output.status.success());
+    let count = |event: &str| run.calls.iter().filter(|c| c.starts_with(event)).count();
+    assert_eq!(count("ayu_event_registerfunction"), 2);
+    assert_eq!(count("ayu_event_addtask "), 12);
+    assert!(count("ayu_event_adddependency") >= 8);
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("Generated tasks 0 to 11 with"));
+
+    // the same seed gives the same graph
+    let again = run_wrapper("generate_random_graph_again", &["--script", "-"], script);
+    assert_eq!(run.calls, again.calls);
+
+    let run = run_wrapper("generate_random_graph_invalid", &["--script", "-"], "generate random density=1.5\n");
+    assert!(String::from_utf8_lossy(&run.output.stderr).contains("<stdin>:1: Error while reading input:\n\tInvalid argument: density=1.5"));
+}
+
+#[test]
+fn generate_without_task_ids_left() {
+    let script = "task id=18446744073709551614\ngenerate forkjoin width=1 depth=1\ntask\ntask\n";
+    let run = run_wrapper("generate_without_task_ids_left", &["--script", "-"], script);
+
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:2: Error while reading input:\n\tThere are not enough task ids left for 3 tasks."), "{}", stderr);
+    assert!(stderr.contains("<stdin>:4: Error while reading input:\n\tThere is no task id left for a new task."), "{}", stderr);
+    assert_eq!(run.calls.iter().filter(|c| c.starts_with("ayu_event_addtask ")).count(), 2);
+}
+
+#[test]
+fn generate_cholesky() {
+    let run = run_wrapper("generate_cholesky", &["--script", "-"], "generate cholesky tiles=2\n");
+
+    assert!(run.output.status.success());
+    assert_eq!(&run.calls[2..], [
+        "ayu_event_registerfunction func_id=0 name=\"POTRF\"",
+        "ayu_event_registerfunction func_id=1 name=\"TRSM\"",
+        "ayu_event_registerfunction func_id=2 name=\"SYRK\"",
+        "ayu_event_addtask task_id=0 func_id=0 priority=1 scope_id=0",
+        "ayu_event_addtask task_id=1 func_id=1 priority=0 scope_id=1",
+        "ayu_event_addtask task_id=2 func_id=2 priority=0 scope_id=0",
+        "ayu_event_addtask task_id=3 func_id=0 priority=1 scope_id=1",
+        "ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef",
+        "ayu_event_adddependency to_id=1 from_id=2 memaddr=0xffffeeef orig_memaddr=0xffffeee",
+        "ayu_event_adddependency to_id=2 from_id=3 memaddr=0xffffeeee orig_memaddr=0xffffeef",
+        "ayu_event_finish",
+    ]);
+}
+
+#[test]
+fn generate_patterns() {
+    let script = "generate pipeline width=3 depth=2\ngenerate forkjoin width=2 depth=1\n";
+    let run = run_wrapper("generate_patterns", &["--script", "-"], script);
+
+    assert!(run.output.status.success());
+    let registered: Vec<_> = run.calls.iter().filter(|c| c.starts_with("ayu_event_registerfunction")).collect();
+    assert_eq!(registered, [
+        "ayu_event_registerfunction func_id=0 name=\"stage_0\"",
+        "ayu_event_registerfunction func_id=1 name=\"stage_1\"",
+        "ayu_event_registerfunction func_id=2 name=\"fork\"",
+        "ayu_event_registerfunction func_id=3 name=\"work\"",
+        "ayu_event_registerfunction func_id=4 name=\"join\"",
+    ]);
+    // the fork-join continues the task ids of the pipeline
+    assert!(run.calls.contains(&"ayu_event_adddependency to_id=6 from_id=7 memaddr=0xffffeeee orig_memaddr=0xffffeef".to_string()));
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("Generated tasks 6 to 9 with 4 dependencies."));
+}
+
+#[test]
+fn import_matrix_market() {
+    let matrix = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("import_matrix_market.mtx");
+    std::fs::write(&matrix, "%%MatrixMarket matrix coordinate real symmetric\n3 3 5\n1 1 2\n2 1 -1\n2 2 2\n3 2 -1\n3 3 2\n").unwrap();
+
+    let script = format!("import order=mindegree {}\n", matrix.display());
+    let run = run_wrapper("import_matrix_market", &["--script", "-"], &script);
+
+    assert!(run.output.status.success());
+    let registered: Vec<_> = run.calls.iter().filter(|c| c.starts_with("ayu_event_registerfunction")).collect();
+    assert_eq!(registered, [
+        "ayu_event_registerfunction func_id=0 name=\"POTRF\"",
+        "ayu_event_registerfunction func_id=1 name=\"TRSM\"",
+        "ayu_event_registerfunction func_id=2 name=\"GEMM\"",
+    ]);
+    let stdout = String::from_utf8_lossy(&run.output.stdout);
+    assert!(stdout.contains("Factor of 3 columns has 5 nonzeros in 2 supernodes."));
+    assert!(stdout.contains("Generated tasks 0 to 3 with 3 dependencies."));
+
+    let run = run_wrapper("import_matrix_market_missing", &["--script", "-"], "import missing.mtx\n");
+    assert!(String::from_utf8_lossy(&run.output.stderr).contains("<stdin>:1: Error while reading input:\n\tUnable to import the matrix from missing.mtx: Unable to read matrix"));
+    assert!(!run.calls.iter().any(|c| c.starts_with("ayu_event_addtask")));
+}
@@ -0,0 +1,46 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use std::{fs, path::Path, time::{Duration, Instant}};
+
+use common::run_wrapper;
+
+#[test]
+fn replay_sends_recorded_events() {
+    let log = Path::new(env!("CARGO_TARGET_TMPDIR")).join("replay_sends_recorded_events.events");
+    let _ = fs::remove_file(&log);
+
+    let script = "function kernel\ntask label=0\ntask\ndep 0 -> 1\nqueue 0\nprerun 0\nrun 0\nfinish\n";
+    let recorded = run_wrapper("replay_record", &["--script", "-", "--record", log.to_str().unwrap()], script);
+    assert!(recorded.output.status.success());
+
+    let replayed = run_wrapper("replay_sends_recorded_events", &["--replay", log.to_str().unwrap(), "--replay-speed", "max"], "");
+    assert!(replayed.output.status.success());
+    assert_eq!(replayed.calls, recorded.calls);
+}
+
+#[test]
+fn replay_keeps_recorded_gaps() {
+    let log = Path::new(env!("CARGO_TARGET_TMPDIR")).join("replay_keeps_recorded_gaps.events");
+    fs::write(&log, "0 10.0 ayu_event_preinit rt=0\n1 10.2 ayu_event_init n_threads=4\n2 10.4 ayu_event_finish\n").unwrap();
+
+    let start = Instant::now();
+    let run = run_wrapper("replay_keeps_recorded_gaps", &["--replay", log.to_str().unwrap(), "--replay-speed", "2"], "");
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls, ["ayu_event_preinit rt=0", "ayu_event_init n_threads=4", "ayu_event_finish"]);
+}
+
+#[test]
+fn replay_reports_invalid_line() {
+    let log = Path::new(env!("CARGO_TARGET_TMPDIR")).join("replay_reports_invalid_line.events");
+    fs::write(&log, "0 10.0 ayu_event_preinit rt=0\n1 10.2 ayu_event_init\n").unwrap();
+
+    let run = run_wrapper("replay_reports_invalid_line", &["--replay", log.to_str().unwrap()], "");
+
+    assert_eq!(run.output.status.code(), Some(1));
+    assert!(run.calls.is_empty());
+    assert!(String::from_utf8_lossy(&run.output.stderr).contains("Invalid event in line 2: Missing argument: n_threads"));
+}
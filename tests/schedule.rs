@@ -0,0 +1,58 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use common::run_wrapper;
+
+/// Four ready tasks on thread 0, the last of them critical, and a fifth depending on the first
+const SCRIPT: &str = "task\ntask\ntask\ntask crit\ntask\ndep 0 -> 4\n";
+
+fn pre_run_calls(calls: &[String]) -> Vec<&str> {
+    calls.iter().filter(|c| c.starts_with("ayu_event_preruntask")).map(String::as_str).collect()
+}
+
+#[test]
+fn schedule_with_configured_policy() {
+    let script = format!("{}schedule\n", SCRIPT);
+    let run = run_wrapper("schedule_with_configured_policy", &["--script", "-", "--scheduler", "lifo"], &script);
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls.iter().filter(|c| c.starts_with("ayu_event_addtasktoqueue")).count(), 4);
+    assert_eq!(pre_run_calls(&run.calls), [
+        "ayu_event_preruntask task_id=3 thread_id=0",
+        "ayu_event_preruntask task_id=2 thread_id=1",
+        "ayu_event_preruntask task_id=1 thread_id=0",
+        "ayu_event_preruntask task_id=0 thread_id=1",
+    ]);
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("Thread 0: tasks 3, 1\nThread 1: tasks 2, 0\n"));
+}
+
+#[test]
+fn schedule_policies() {
+    let script = format!("{}schedule critical\n", SCRIPT);
+    let run = run_wrapper("schedule_critical_first", &["--script", "-"], &script);
+    assert_eq!(pre_run_calls(&run.calls)[0], "ayu_event_preruntask task_id=3 thread_id=0");
+
+    let script = format!("{}schedule affinity\n", SCRIPT);
+    let run = run_wrapper("schedule_affinity", &["--script", "-"], &script);
+    assert!(pre_run_calls(&run.calls).iter().all(|c| c.ends_with("thread_id=0")));
+
+    // nothing is ready once all tasks have been scheduled
+    let script = format!("{}schedule\nschedule\n", SCRIPT);
+    let run = run_wrapper("schedule_nothing_ready", &["--script", "-"], &script);
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("No task is ready."));
+}
+
+#[test]
+fn simulate_with_policy() {
+    let script = "generate forkjoin width=4 depth=1\nsimulate policy=affinity work=2\n";
+    let run = run_wrapper("simulate_with_policy", &["--script", "-"], script);
+
+    assert!(run.output.status.success());
+    // the work tasks were created for both threads, and each thread runs its own
+    for call in pre_run_calls(&run.calls) {
+        let task_id: u64 = call.split(['=', ' ']).nth(2).unwrap().parse().unwrap();
+        assert!(call.ends_with(&format!("thread_id={}", task_id % 2)), "{} runs on another thread", call);
+    }
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("makespan: 6 ticks"));
+}
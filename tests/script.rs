@@ -0,0 +1,184 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use common::run_wrapper;
+
+const SCRIPT: &str = "\
+# two tasks with a dependency
+function kernel
+task crit thread=1 label=0
+task
+dep 0 -> 1
+run 7
+queue 0
+finish
+";
+
+#[test]
+fn script_from_stdin() {
+    let run = run_wrapper("script_from_stdin", &["--script", "-"], SCRIPT);
+
+    // the script continues after an error, but the session still failed
+    assert_eq!(run.output.status.code(), Some(1));
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_registerfunction func_id=0 name=\"kernel\"",
+        "ayu_event_addtask task_id=0 func_id=0 priority=1 scope_id=1",
+        "ayu_event_addtask task_id=1 func_id=1 priority=0 scope_id=0",
+        "ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef",
+        "ayu_event_addtasktoqueue task_id=0 thread_id=1",
+        "ayu_event_finish",
+    ]);
+
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:6: Error while reading input:\n\tTask with id: 7 not found."));
+}
+
+#[test]
+fn script_aborts_on_first_error() {
+    let run = run_wrapper("script_aborts_on_first_error", &["--script", "-", "--abort-on-error"], SCRIPT);
+
+    assert_eq!(run.output.status.code(), Some(1));
+    assert_eq!(run.calls.len(), 7);
+    assert_eq!(&run.calls[5..], ["ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef", "ayu_event_finish"]);
+}
+
+#[test]
+fn script_reports_parse_errors_with_line() {
+    let run = run_wrapper("script_reports_parse_errors_with_line", &["--script", "-"], "\n\njump 3\nbarrier\n");
+
+    assert_eq!(run.output.status.code(), Some(1));
+    assert_eq!(&run.calls[2..], ["ayu_event_barrier", "ayu_event_finish"]);
+    assert!(String::from_utf8_lossy(&run.output.stderr).contains("<stdin>:3: Error while reading input:\n\tUnknown command: jump"));
+}
+
+#[test]
+fn record_logs_every_event() {
+    let log = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("record_logs_every_event.events");
+    let _ = std::fs::remove_file(&log);
+
+    let run = run_wrapper("record_logs_every_event", &["--script", "-", "--record", log.to_str().unwrap()], SCRIPT);
+    assert_eq!(run.calls.len(), 8);
+
+    let recorded = std::fs::read_to_string(&log).unwrap();
+    let mut lines = recorded.lines();
+    assert!(lines.next().unwrap().starts_with('#'));
+
+    let events: Vec<_> = lines.enumerate()
+        .map(|(seq, line)| {
+            let (n, rest) = line.split_once(' ').unwrap();
+            assert_eq!(n, seq.to_string());
+            rest.split_once(' ').unwrap().1.to_string()
+        })
+        .collect();
+    assert_eq!(events, run.calls);
+}
+
+#[test]
+fn dependency_cycles_are_refused() {
+    let script = "task\ntask\ntask\ndep 0 -> 1\ndep 1 -> 2\ndep 2 -> 0\n";
+
+    let run = run_wrapper("dependency_cycles_are_refused", &["--script", "-"], script);
+    assert_eq!(run.calls.len(), 8);
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:6: Error while reading input:\n\tDependency 2 -> 0 would create the cycle 2 -> 0 -> 1 -> 2."));
+
+    let run = run_wrapper("dependency_cycles_are_allowed", &["--script", "-", "--allow-cycles"], script);
+    assert_eq!(run.calls.len(), 9);
+    assert_eq!(run.calls[7], "ayu_event_adddependency to_id=2 from_id=0 memaddr=0xffffeeee orig_memaddr=0xffffeee");
+}
+
+#[test]
+fn remove_policies() {
+    let script = "task\ntask\ntask\ndep 0 -> 1\ndep 1 -> 2\nremove 1 refuse\nremove 0 cascade\n";
+    let run = run_wrapper("remove_policies", &["--script", "-"], script);
+
+    assert_eq!(&run.calls[7..], [
+        "ayu_event_removetask task_id=0",
+        "ayu_event_removetask task_id=1",
+        "ayu_event_removetask task_id=2",
+        "ayu_event_finish",
+    ]);
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:6: Error while reading input:\n\tTask 1 still has the dependents 2, remove them first or use the cascade or detach policy."));
+}
+
+#[test]
+fn duplicate_dependencies() {
+    let script = "task\ntask\ndep 0 -> 1\ndep 0 -> 1\nundep 0 -> 1\ndep 0 -> 1\n";
+    let dep = "ayu_event_adddependency to_id=0 from_id=1 memaddr=0xffffeeee orig_memaddr=0xffffeef";
+    let finish = "ayu_event_finish";
+
+    let run = run_wrapper("duplicate_dependencies_error", &["--script", "-"], script);
+    assert_eq!(&run.calls[4..], [dep, dep, finish]);
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:4: Error while reading input:\n\tDependency 0 -> 1 already exists."));
+
+    let run = run_wrapper("duplicate_dependencies_ignore", &["--script", "-", "--on-duplicate", "ignore"], script);
+    assert_eq!(&run.calls[4..], [dep, dep, finish]);
+    assert!(run.output.stderr.is_empty());
+
+    let run = run_wrapper("duplicate_dependencies_resend", &["--script", "-", "--on-duplicate", "resend"], script);
+    assert_eq!(&run.calls[4..], [dep, dep, dep, finish]);
+}
+
+#[test]
+fn wait_on_holds_back_dependents() {
+    let script = "\
+task
+task
+task
+dep 0 -> 1
+dep 1 -> 2
+queue 0
+waiton 0
+queue 1
+queue 2
+prerun 0
+run 0
+postrun 0
+waiton children 0
+waiton children 2
+waiton 9
+";
+    let run = run_wrapper("wait_on_holds_back_dependents", &["--script", "-"], script);
+
+    assert_eq!(&run.calls[7..], [
+        "ayu_event_addtasktoqueue task_id=0 thread_id=0",
+        "ayu_event_waiton task_id=0",
+        "ayu_event_preruntask task_id=0 thread_id=0",
+        "ayu_event_runtask task_id=0",
+        "ayu_event_postruntask task_id=0",
+        "ayu_event_addtasktoqueue task_id=1 thread_id=0",
+        "ayu_event_addtasktoqueue task_id=2 thread_id=0",
+        "ayu_event_waiton task_id=1",
+        "ayu_event_finish",
+    ]);
+
+    let stdout = String::from_utf8_lossy(&run.output.stdout);
+    assert!(stdout.contains("Task 2 depends on task 0, which is waited on. Holding back its event until task 0 has finished."));
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:14: Error while reading input:\n\tTask with id: 2 has no children."));
+    assert!(stderr.contains("<stdin>:15: Error while reading input:\n\tTask with id: 9 not found."));
+}
+
+#[test]
+fn barriers_end_phases() {
+    let dot = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("barriers_end_phases.dot");
+    let script = format!("task\ntask\nbarrier\ntask\ndep 1 -> 2\nexport {}\n", dot.display());
+
+    let run = run_wrapper("barriers_end_phases", &["--script", "-"], &script);
+    assert_eq!(run.calls[4], "ayu_event_barrier");
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("Tasks outstanding at the end of phase 0: 0, 1"));
+
+    let dot = std::fs::read_to_string(dot).unwrap();
+    assert!(dot.contains("subgraph cluster_phase_0 {\n        label = \"phase 0\";\n        0 [label=\"0\"];\n        1 [label=\"1\"];\n    }"));
+    assert!(dot.contains("subgraph cluster_phase_1 {\n        label = \"phase 1\";\n        2 [label=\"2\"];\n    }"));
+
+    let run = run_wrapper("barriers_refused", &["--script", "-", "--at-barrier", "refuse"], &script);
+    assert!(!run.calls.iter().any(|c| c == "ayu_event_barrier"));
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("<stdin>:3: Error while reading input:\n\tTasks 0, 1 have not finished, phase 0 cannot end yet."));
+}
@@ -0,0 +1,79 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+};
+
+use common::{fake_ayudame, run_wrapper};
+
+#[test]
+fn quit_sends_finish_once() {
+    let run = run_wrapper("quit_sends_finish_once", &[], "task\n\n\n\nq\ntask\n");
+
+    assert!(run.output.status.success());
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=0",
+        "ayu_event_init n_threads=2",
+        "ayu_event_addtask task_id=0 func_id=0 priority=0 scope_id=0",
+        "ayu_event_finish",
+    ]);
+
+    let run = run_wrapper("quit_after_finish", &["--script", "-"], "finish\nquit\nbarrier\n");
+    assert!(run.output.status.success());
+    assert_eq!(run.calls.iter().filter(|c| *c == "ayu_event_finish").count(), 1);
+    assert_eq!(run.calls.last().unwrap(), "ayu_event_finish");
+}
+
+#[test]
+fn drain_removes_all_tasks() {
+    let script = "task\ntask\ntask\ndep 0 -> 1\nqueue 0\nprerun 0\nqueue 2\nwaiton 0\nqueue 1\n";
+    let run = run_wrapper("drain_removes_all_tasks", &["--script", "-", "--drain"], script);
+
+    assert!(run.output.status.success());
+    assert_eq!(&run.calls[10..], [
+        "ayu_event_runtask task_id=0",
+        "ayu_event_postruntask task_id=0",
+        "ayu_event_removetask task_id=0",
+        "ayu_event_removetask task_id=1",
+        "ayu_event_preruntask task_id=2 thread_id=0",
+        "ayu_event_runtask task_id=2",
+        "ayu_event_postruntask task_id=2",
+        "ayu_event_removetask task_id=2",
+        "ayu_event_finish",
+    ]);
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("Dropping 1 held back events."));
+}
+
+#[test]
+fn interrupt_sends_finish() {
+    let log = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("interrupt_sends_finish.log");
+    let _ = std::fs::remove_file(&log);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ayudame_wrapper"))
+        .arg("--ayudame-lib")
+        .arg(fake_ayudame())
+        .env("FAKE_AYUDAME_LOG", &log)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // stdin is kept open, so only the signal ends the session. The signal handler is installed before the menu is shown
+    let stdin = child.stdin.take();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+    assert!(stdout.any(|line| line.unwrap().starts_with("Options:")));
+    let status = Command::new("kill").args(["-INT", &child.id().to_string()]).status().unwrap();
+    assert!(status.success());
+
+    let output = child.wait_with_output().unwrap();
+    drop((stdin, stdout));
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Interrupted, shutting down."));
+
+    let calls = std::fs::read_to_string(&log).unwrap();
+    assert_eq!(calls.lines().collect::<Vec<_>>(), ["ayu_event_preinit rt=0", "ayu_event_init n_threads=2", "ayu_event_finish"]);
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use common::{assert_parents_finish_first, run_wrapper};
+
+#[test]
+fn simulate_fork_join() {
+    let script = "generate forkjoin width=2 depth=1\nsimulate duration=1 work=3\n";
+    let run = run_wrapper("simulate_fork_join", &["--script", "-"], script);
+
+    assert!(run.output.status.success());
+    let lifecycle: Vec<_> = run.calls.iter().skip_while(|c| !c.starts_with("ayu_event_addtasktoqueue")).collect();
+    assert_eq!(lifecycle, [
+        "ayu_event_addtasktoqueue task_id=0 thread_id=0",
+        "ayu_event_preruntask task_id=0 thread_id=0",
+        "ayu_event_runtask task_id=0",
+        "ayu_event_postruntask task_id=0",
+        "ayu_event_removetask task_id=0",
+        "ayu_event_addtasktoqueue task_id=1 thread_id=0",
+        "ayu_event_addtasktoqueue task_id=2 thread_id=0",
+        "ayu_event_preruntask task_id=1 thread_id=0",
+        "ayu_event_runtask task_id=1",
+        "ayu_event_preruntask task_id=2 thread_id=1",
+        "ayu_event_runtask task_id=2",
+        "ayu_event_postruntask task_id=1",
+        "ayu_event_removetask task_id=1",
+        "ayu_event_postruntask task_id=2",
+        "ayu_event_removetask task_id=2",
+        "ayu_event_addtasktoqueue task_id=3 thread_id=1",
+        "ayu_event_preruntask task_id=3 thread_id=0",
+        "ayu_event_runtask task_id=3",
+        "ayu_event_postruntask task_id=3",
+        "ayu_event_removetask task_id=3",
+        "ayu_event_finish",
+    ]);
+    assert!(String::from_utf8_lossy(&run.output.stdout).contains("\
+Simulated 4 tasks on 2 threads, makespan: 5 ticks
+\tThread 0: busy for 5 ticks, utilisation 100.0%
+\tThread 1: busy for 3 ticks, utilisation 60.0%
+"));
+}
+
+#[test]
+fn simulate_runs_parents_first() {
+    let script = "generate cholesky tiles=4\nsimulate duration=2 GEMM=5\n";
+    let run = run_wrapper("simulate_runs_parents_first", &["--script", "-", "--threads", "4"], script);
+
+    assert!(run.output.status.success());
+    assert_parents_finish_first(&run.calls);
+    assert_eq!(run.calls.iter().filter(|c| c.starts_with("ayu_event_removetask")).count(), 20);
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "libayudame")]
+
+mod common;
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{fake_ayudame, run_wrapper};
+
+#[test]
+fn manual_startup_waits_for_commands() {
+    let run = run_wrapper("manual_startup_waits_for_commands", &["--startup", "manual"], "task\n\n\n\npreinit 3\ninit 4\ntask\n\n\n\n");
+
+    assert!(!run.output.status.success());
+    assert!(String::from_utf8_lossy(&run.output.stderr).contains("Events can only be sent after Init."));
+    assert_eq!(run.calls, [
+        "ayu_event_preinit rt=3",
+        "ayu_event_init n_threads=4",
+        "ayu_event_addtask task_id=0 func_id=0 priority=0 scope_id=0",
+        "ayu_event_finish",
+    ]);
+}
+
+#[test]
+fn manual_startup_with_chaos_sends_init_first() {
+    let run = run_wrapper("manual_startup_with_chaos_sends_init_first", &["--startup", "manual", "--chaos"], "function kernel\ninit\npreinit\n");
+
+    assert_eq!(run.calls, [
+        "ayu_event_registerfunction func_id=0 name=\"kernel\"",
+        "ayu_event_init n_threads=2",
+        "ayu_event_preinit rt=0",
+        "ayu_event_finish",
+    ]);
+    let stderr = String::from_utf8_lossy(&run.output.stderr);
+    assert!(stderr.contains("breaking rule \"events follow init\""));
+    assert!(stderr.contains("breaking rule \"init follows pre_init\""));
+}
+
+#[test]
+fn delayed_startup() {
+    let start = Instant::now();
+    let run = run_wrapper("delayed_startup", &["--startup", "delay=200"], "");
+
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    assert_eq!(run.calls, ["ayu_event_preinit rt=0", "ayu_event_init n_threads=2", "ayu_event_finish"]);
+}
+
+#[test]
+fn keep_alive_while_idle() {
+    let log = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("keep_alive_while_idle.log");
+    let _ = std::fs::remove_file(&log);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ayudame_wrapper"))
+        .arg("--ayudame-lib")
+        .arg(fake_ayudame())
+        .args(["--keep-alive", "50"])
+        .env("FAKE_AYUDAME_LOG", &log)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // no input for a while, then finish, after which no more keep-alive events are sent
+    thread::sleep(Duration::from_millis(400));
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(b"finish\n").unwrap();
+    thread::sleep(Duration::from_millis(200));
+    stdin.write_all(b"print\n").unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let calls = std::fs::read_to_string(&log).unwrap();
+    let calls: Vec<_> = calls.lines().collect();
+    assert_eq!(&calls[..2], ["ayu_event_preinit rt=0", "ayu_event_init n_threads=2"]);
+    assert_eq!(calls.last(), Some(&"ayu_event_finish"));
+
+    let barriers = calls.iter().filter(|c| **c == "ayu_event_barrier").count();
+    assert!(barriers >= 3 && barriers == calls.len() - 3, "{:?}", calls);
+    // every keep-alive barrier ended a phase
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&format!("Phase: {}", barriers)));
+}